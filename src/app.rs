@@ -1,26 +1,88 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
 use crate::{
-    dive,
-    stats::{Stats, StatsData, StatsOutput},
+    common::{self, Density, Depth, Seconds, TemperatureUnit, VerticalSpeed},
+    dive::{Dive, TimelinePoint, TissuesSnapshot},
+    stats::{self, DiveSortKey, Stats, StatsConfig, StatsData},
 };
 use eframe::egui::{self, InnerResponse, Ui};
+use egui_plot::{
+    Bar, BarChart, HLine, Legend, Line, MarkerShape, Plot, PlotPoint, PlotPoints, Points, Text,
+    VLine,
+};
 use rfd::FileDialog;
-use std::{error::Error, sync::Arc};
-use std::{future::Future, path::PathBuf};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::{thread, thread::JoinHandle, time::Duration};
 
-#[derive(Clone)]
 pub struct App {
     title: String,
     stats: Stats,
-    stats_output: StatsOutput,
     config: AppConfig,
     state: AppState,
+    // analysis running in background, error message as result
+    loading: Option<JoinHandle<Result<(), String>>>,
+    // selected dive analysed again with samples for profile and tissues,
+    // stats keep only dive summaries
+    profile: Option<(DiveId, Result<Arc<Dive>, String>)>,
+    // dives table order as positions in stats dives
+    dive_order: Option<(DiveOrderKey, Arc<Vec<usize>>)>,
+    // tissues replayed for selected dive and time, none without replay data
+    tissues: Option<(TissuesKey, Option<Arc<TissuesSnapshot>>)>,
 }
 
+// source, repetition group, dive index in group
+type DiveId = (String, usize, usize);
+
+// dive and time of replayed tissues
+type TissuesKey = (DiveId, Seconds);
+
+// sort key, descending and number of dives the order was calculated for
+type DiveOrderKey = (DiveSortKey, bool, usize);
+
+// dives table header, sort key and column width
+const DIVE_COLUMNS: [(&str, Option<DiveSortKey>, f32); 27] = [
+    ("#", None, 30.),
+    ("No.", Some(DiveSortKey::Number), 50.),
+    ("Date", Some(DiveSortKey::Date), 120.),
+    ("Duration", Some(DiveSortKey::Duration), 80.),
+    ("Max depth", Some(DiveSortKey::Depth), 85.),
+    ("Deco", Some(DiveSortKey::Deco), 60.),
+    ("GF99", Some(DiveSortKey::GF99), 60.),
+    ("Surface GF", Some(DiveSortKey::GFSurf), 90.),
+    ("File", Some(DiveSortKey::Source), 150.),
+    ("Site", None, 140.),
+    ("Buddies", None, 140.),
+    ("Water", None, 60.),
+    ("Visibility", None, 70.),
+    ("Rating", None, 55.),
+    ("Surface interval", None, 110.),
+    ("GF", None, 55.),
+    ("Max ppO2", None, 70.),
+    ("CNS", None, 50.),
+    ("OTU", None, 50.),
+    ("RMV", None, 80.),
+    ("Min NDL", None, 65.),
+    ("Max TTS", None, 65.),
+    ("Max ceiling", None, 80.),
+    ("Max ascent", None, 95.),
+    ("Ceiling", None, 110.),
+    ("Gas", None, 100.),
+    ("Time below", None, 220.),
+];
+
 #[derive(Clone)]
 struct AppState {
     error: Option<AppError>,
+    new_treshold_depth: Depth,
+    new_ascent_rate_limit: (Depth, VerticalSpeed),
+    dive_sort: (DiveSortKey, bool),
+    // dive with profile shown
+    selected_dive: Option<DiveId>,
+    // selected dive time for tissues state
+    tissues_time: Seconds,
+    // loading cancelled, some files not analysed until sources reloaded
+    incomplete: bool,
 }
 
 #[derive(Clone)]
@@ -30,101 +92,352 @@ struct AppError {
 
 #[derive(Clone)]
 struct AppConfig {
-    path: Option<String>,
-    gradient_factors: (u8, u8),
+    // analysed files and directories
+    sources: Vec<String>,
+    stats_config: StatsConfig,
 }
 
 impl Default for App {
     fn default() -> Self {
+        let stats_config = StatsConfig::default();
         Self {
             title: "Dive reporter".to_owned(),
-            stats: Stats::new(),
-            stats_output: vec![],
+            stats: Stats::with_config(stats_config.clone()),
             config: AppConfig {
-                path: None,
-                gradient_factors: (30, 70),
+                sources: vec![],
+                stats_config,
             },
-            state: AppState { error: None },
+            state: AppState {
+                error: None,
+                new_treshold_depth: 50.,
+                new_ascent_rate_limit: (30., 18.),
+                dive_sort: (DiveSortKey::Source, false),
+                selected_dive: None,
+                tissues_time: 0,
+                incomplete: false,
+            },
+            loading: None,
+            profile: None,
+            dive_order: None,
+            tissues: None,
         }
     }
 }
 
 impl eframe::App for App {
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.poll_loading(ctx);
+        egui::SidePanel::left("side_panel")
+            .resizable(false)
+            .exact_width(300.)
+            .show(ctx, |ui| {
+                egui::ScrollArea::vertical().show(ui, |ui| self.render_side_panel(ui));
+            });
         egui::CentralPanel::default().show(ctx, |ui| {
-            ui.heading(&self.title);
-            ui.separator();
-
-            // config
-            self.render_pair(
-                ui,
-                "Path:",
-                &self.config.path.clone().unwrap_or("-".to_string()),
-            );
-            ui.separator();
-
-            // open file btn
-            self.render_file_btns(ui);
-
-            ui.separator();
-
-            // stats container
-            match &self.state.error {
-                None => {
-                    // let stats = self.stats.clone();
-                    let stats_arc = Arc::clone(&self.stats.stats_data);
-                    let stats_guard = stats_arc.lock().unwrap();
-                    let stats = stats_guard;
-                    if stats.dives_no > 0 {
-                        self.state.error = None;
-                        self.render_stats(ui, &*stats)
+            let stats_arc = Arc::clone(&self.stats.stats_data);
+            let stats = stats_arc.lock().unwrap();
+            if self.state.error.is_none() && !stats.dives.is_empty() {
+                if let Some(dive_id) = self.selected_dive(&stats) {
+                    match self.profile_dive(dive_id) {
+                        Ok(dive) => self.render_profile(ui, &dive),
+                        Err(text) => {
+                            ui.colored_label(egui::Color32::RED, text);
+                        }
                     }
+                    ui.separator();
                 }
-                Some(err) => {
-                    self.render_error(ui, &err);
-                }
+                self.render_dives(ui, &stats);
             }
         });
     }
 }
 
 impl App {
-    pub fn init(&self) -> Result<(), eframe::Error> {
+    pub fn new(path: Option<String>, stats_config: StatsConfig) -> Self {
+        let mut app = Self::default();
+        app.config.stats_config = stats_config;
+        app.stats = Stats::with_config(app.config.stats_config.clone());
+        if let Some(path) = path {
+            app.add_sources(vec![PathBuf::from(path)]);
+        }
+        app
+    }
+
+    pub fn init(self) -> Result<(), eframe::Error> {
         let options = eframe::NativeOptions {
-            viewport: egui::ViewportBuilder::default().with_inner_size([300.0, 600.0]),
+            viewport: egui::ViewportBuilder::default().with_inner_size([1100.0, 700.0]),
             follow_system_theme: false,
             default_theme: eframe::Theme::Dark,
             ..Default::default()
         };
-        eframe::run_native(
-            "Dive reporter",
-            options,
-            Box::new(|_cc| Box::<App>::default()),
-        )
+        eframe::run_native("Dive reporter", options, Box::new(|_cc| Box::new(self)))
+    }
+
+    fn render_side_panel(&mut self, ui: &mut Ui) {
+        ui.heading(&self.title);
+        ui.separator();
+
+        // config
+        self.render_sources(ui);
+        self.render_config(ui);
+        ui.separator();
+
+        // open file btn
+        self.render_file_btns(ui);
+        self.render_loading(ui);
+
+        ui.separator();
+
+        // stats container
+        match &self.state.error {
+            None => {
+                // let stats = self.stats.clone();
+                let stats_arc = Arc::clone(&self.stats.stats_data);
+                let stats_guard = stats_arc.lock().unwrap();
+                let stats = stats_guard;
+                if stats.dives_no > 0 {
+                    self.state.error = None;
+                    self.render_stats(ui, &stats)
+                }
+                if !stats.skipped.is_empty() {
+                    ui.separator();
+                    self.render_skipped(ui, &stats);
+                }
+                let warnings = stats.warnings();
+                if !warnings.is_empty() {
+                    ui.separator();
+                    self.render_warnings(ui, &warnings);
+                }
+            }
+            Some(err) => {
+                self.render_error(ui, err);
+            }
+        }
     }
 
     fn render_file_btns(&mut self, ui: &mut Ui) {
-        if ui.button("📂 Open UDDF file").clicked() {
-            let file = FileDialog::new()
+        let enabled = self.loading.is_none();
+        if ui
+            .add_enabled(enabled, egui::Button::new("📂 Add UDDF files"))
+            .clicked()
+        {
+            let files = FileDialog::new()
                 .set_directory("/Documents/dive-reporter-uddf")
-                .pick_file();
+                .pick_files();
 
-            if let Some(file_path) = file {
-                self.run_stats(&file_path);
+            if let Some(files) = files {
+                self.add_sources(files);
             }
         }
-        if ui.button("Open multiple files").clicked() {
+        if ui
+            .add_enabled(enabled, egui::Button::new("📁 Add folders"))
+            .clicked()
+        {
             let dirs = FileDialog::new()
                 .set_directory("/Documents/dive-reporter-uddf")
                 .pick_folders();
 
             if let Some(dirs) = dirs {
-                for dir in dirs {
-                    dbg!(&dir);
-                    self.run_stats(&dir);
+                self.add_sources(dirs);
+            }
+        }
+    }
+
+    fn render_sources(&mut self, ui: &mut Ui) {
+        ui.label("Sources:");
+        if self.config.sources.is_empty() {
+            ui.label("-");
+        }
+        let mut removed: Option<usize> = None;
+        for (i, source) in self.config.sources.iter().enumerate() {
+            ui.horizontal(|ui| {
+                if ui
+                    .add_enabled(self.loading.is_none(), egui::Button::new("✖").small())
+                    .on_hover_text("Remove source")
+                    .clicked()
+                {
+                    removed = Some(i);
+                }
+                ui.label(Self::file_name(source)).on_hover_text(source);
+            });
+        }
+        if let Some(i) = removed {
+            self.remove_source(i);
+        }
+    }
+
+    fn render_config(&mut self, ui: &mut Ui) {
+        let stats_config = &mut self.config.stats_config;
+        let (gf_low, gf_high) = &mut stats_config.gradient_factors;
+        ui.horizontal(|ui| {
+            ui.label("GF:");
+            ui.add(egui::DragValue::new(gf_low).clamp_range(1..=100));
+            ui.label("/");
+            ui.add(egui::DragValue::new(gf_high).clamp_range(1..=100));
+        });
+        if *gf_low > *gf_high {
+            *gf_high = *gf_low;
+        }
+        ui.checkbox(
+            &mut stats_config.use_dive_gradient_factors,
+            "Use GF from dive data",
+        );
+        let mut buddy = stats_config.buddy.clone().unwrap_or_default();
+        ui.horizontal(|ui| {
+            ui.label("Buddy:");
+            ui.text_edit_singleline(&mut buddy)
+                .on_hover_text("Only dives with buddy whose name contains text, all if empty");
+        });
+        stats_config.buddy = match buddy.trim().is_empty() {
+            true => None,
+            false => Some(buddy),
+        };
+        let stats_config = &mut self.config.stats_config;
+        ui.horizontal(|ui| {
+            ui.label("Max ppO2:");
+            ui.add(
+                egui::DragValue::new(&mut stats_config.pp_o2_max)
+                    .clamp_range(1.0..=2.0)
+                    .speed(0.01)
+                    .suffix("bar"),
+            )
+            .on_hover_text("ppO2 limit for gas MOD warnings");
+        });
+        ui.horizontal(|ui| {
+            ui.label("Temperature:");
+            for unit in [TemperatureUnit::Celsius, TemperatureUnit::Fahrenheit] {
+                ui.selectable_value(&mut stats_config.temperature_unit, unit, unit.symbol());
+            }
+        });
+        self.render_environment_config(ui);
+        self.render_service_interval_config(ui);
+        self.render_treshold_depths_editor(ui);
+        self.render_ascent_rate_limits_editor(ui);
+        let enabled = !self.config.sources.is_empty() && self.loading.is_none();
+        if ui
+            .add_enabled(enabled, egui::Button::new("Apply"))
+            .clicked()
+        {
+            self.reload_sources();
+        }
+    }
+
+    fn render_environment_config(&mut self, ui: &mut Ui) {
+        let stats_config = &mut self.config.stats_config;
+        ui.label("For dives without environment data:");
+        ui.horizontal(|ui| {
+            ui.label("Surface pressure:");
+            ui.add(
+                egui::DragValue::new(&mut stats_config.surface_pressure)
+                    .clamp_range(500..=1500)
+                    .suffix("mbar"),
+            );
+        });
+        let water_label = |water_density: Option<Density>| match water_density {
+            None => "Default".to_string(),
+            Some(density) if density == common::SALT_WATER_DENSITY => "Salt".to_string(),
+            Some(density) if density == common::FRESH_WATER_DENSITY => "Fresh".to_string(),
+            Some(density) => format!("{density}kg/m³"),
+        };
+        ui.horizontal(|ui| {
+            ui.label("Water:");
+            egui::ComboBox::from_id_source("water_density")
+                .selected_text(water_label(stats_config.water_density))
+                .show_ui(ui, |ui| {
+                    for water_density in [
+                        None,
+                        Some(common::SALT_WATER_DENSITY),
+                        Some(common::FRESH_WATER_DENSITY),
+                    ] {
+                        ui.selectable_value(
+                            &mut stats_config.water_density,
+                            water_density,
+                            water_label(water_density),
+                        );
+                    }
+                });
+        });
+    }
+
+    fn render_service_interval_config(&mut self, ui: &mut Ui) {
+        let stats_config = &mut self.config.stats_config;
+        let mut service_dives = stats_config.service_dives.unwrap_or(0);
+        let mut service_days = stats_config.service_days.unwrap_or(0);
+        ui.horizontal(|ui| {
+            ui.label("Service interval:");
+            ui.add(egui::DragValue::new(&mut service_dives).suffix(" dives"));
+            ui.add(egui::DragValue::new(&mut service_days).suffix(" days"));
+        })
+        .response
+        .on_hover_text("Equipment service warnings, 0 to disable");
+        stats_config.service_dives = (service_dives > 0).then_some(service_dives);
+        stats_config.service_days = (service_days > 0).then_some(service_days);
+    }
+
+    fn render_treshold_depths_editor(&mut self, ui: &mut Ui) {
+        let stats_config = &mut self.config.stats_config;
+        ui.label("Time below depths:");
+        let mut removed: Option<usize> = None;
+        ui.horizontal_wrapped(|ui| {
+            for (i, depth) in stats_config.treshold_depths.iter().enumerate() {
+                if ui.small_button(format!("{depth}m ✖")).clicked() {
+                    removed = Some(i);
                 }
             }
+        });
+        if let Some(i) = removed {
+            stats_config.treshold_depths.remove(i);
         }
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::DragValue::new(&mut self.state.new_treshold_depth)
+                    .clamp_range(0..=300)
+                    .suffix("m"),
+            );
+            if ui.button("Add").clicked() {
+                let mut depths = stats_config.treshold_depths.clone();
+                depths.push(self.state.new_treshold_depth);
+                stats_config.treshold_depths = stats::normalize_treshold_depths(depths);
+            }
+        });
+    }
+
+    fn render_ascent_rate_limits_editor(&mut self, ui: &mut Ui) {
+        let stats_config = &mut self.config.stats_config;
+        ui.label("Ascent rate limits:");
+        let mut removed: Option<usize> = None;
+        ui.horizontal_wrapped(|ui| {
+            for (i, (depth, rate)) in stats_config.ascent_rate_limits.iter().enumerate() {
+                if ui
+                    .small_button(format!("{depth}m+: {rate}m/min ✖"))
+                    .clicked()
+                {
+                    removed = Some(i);
+                }
+            }
+        });
+        if let Some(i) = removed {
+            stats_config.ascent_rate_limits.remove(i);
+        }
+        let (new_depth, new_rate) = &mut self.state.new_ascent_rate_limit;
+        ui.horizontal(|ui| {
+            ui.label("From");
+            ui.add(
+                egui::DragValue::new(new_depth)
+                    .clamp_range(0..=300)
+                    .suffix("m"),
+            );
+            ui.add(
+                egui::DragValue::new(new_rate)
+                    .clamp_range(1..=100)
+                    .suffix("m/min"),
+            );
+            if ui.button("Add").clicked() {
+                let mut limits = stats_config.ascent_rate_limits.clone();
+                limits.push((*new_depth, *new_rate));
+                stats_config.ascent_rate_limits = stats::normalize_ascent_rate_limits(limits);
+            }
+        });
     }
 
     fn render_stats(&mut self, ui: &mut Ui, stats: &StatsData) {
@@ -132,8 +445,12 @@ impl App {
         let gf_surf_max = stats.gf_surf_max.round().to_string();
         let gf_99_max = stats.gf_99_max.round().to_string();
         let gf_end_max = stats.gf_end_max.round().to_string();
+        let cns_max = stats.cns_max.round().to_string();
 
         ui.vertical(|ui| {
+            for (_, diver) in stats.divers.iter() {
+                self.render_pair(ui, "Diver:", &diver.name_with_certifications());
+            }
             self.render_pair(ui, "Dives:", &stats.dives_no.to_string());
             self.render_pair(
                 ui,
@@ -150,18 +467,735 @@ impl App {
             self.render_pair(ui, "Max surface GF:", &format!("{gf_surf_max}%"));
             self.render_pair(ui, "Max GF99:", &format!("{gf_99_max}%"));
             self.render_pair(ui, "Max end GF:", &format!("{gf_end_max}%"));
+            self.render_pair(ui, "Max CNS:", &format!("{cns_max}%"));
+            self.render_pair(ui, "Total OTU:", &stats.otu_total.round().to_string());
+            self.render_pair(
+                ui,
+                "Max OTU per day:",
+                &stats.otu_per_day_max().round().to_string(),
+            );
+            self.render_pair(
+                ui,
+                "Max ascent rate:",
+                &format!("{:.1}m/min", stats.ascent_rate_max),
+            );
+            self.render_pair(
+                ui,
+                "Max descent rate:",
+                &format!("{:.1}m/min", stats.descent_rate_max),
+            );
+            self.render_pair(
+                ui,
+                "Fast ascent dives:",
+                &stats.fast_ascent_dives_no.to_string(),
+            );
+            self.render_pair(
+                ui,
+                "Fast ascent time:",
+                &Stats::seconds_to_readable(stats.fast_ascent_time),
+            );
+            self.render_pair(ui, "Max TTS:", &format!("{}min", stats.tts_max));
+            self.render_pair(ui, "Max ceiling:", &format!("{:.1}m", stats.ceiling_max));
+            self.render_pair(
+                ui,
+                "Ceiling violations:",
+                &format!(
+                    "{} in {} dive(s)",
+                    stats.ceiling_violations_no, stats.ceiling_violation_dives_no
+                ),
+            );
+            self.render_pair(
+                ui,
+                "Gas warnings:",
+                &Stats::gas_warnings_summary_readable(stats),
+            );
+            self.render_pair(ui, "Loop dives:", &stats.loop_dives_no.to_string());
+            self.render_pair(
+                ui,
+                "Loop / OC time:",
+                &format!(
+                    "{} / {}",
+                    Stats::seconds_to_readable(stats.loop_time),
+                    Stats::seconds_to_readable(stats.open_circuit_time)
+                ),
+            );
+            self.render_pair(ui, "Bailouts:", &stats.bailouts_no.to_string());
+            let unit = self.config.stats_config.temperature_unit;
+            self.render_pair(
+                ui,
+                "Min water temp:",
+                &Stats::temperature_min_readable(stats, unit),
+            );
+            self.render_pair(
+                ui,
+                "Avg water temp:",
+                &Stats::average_temperature_readable(stats.temperature_avg, unit),
+            );
+            if !stats.temperature_per_month.is_empty() {
+                self.render_pair(ui, "Avg water temp by month:", "");
+                for (month, average) in stats.temperature_per_month.iter() {
+                    ui.indent("", |ui| {
+                        self.render_pair(
+                            ui,
+                            &format!("-{month}:"),
+                            &Stats::average_temperature_readable(*average, unit),
+                        );
+                    });
+                }
+            }
+            let consumption = &stats.consumption;
+            self.render_pair(
+                ui,
+                "Avg RMV:",
+                &Stats::average_readable(consumption.rmv, 1, "l/min"),
+            );
+            self.render_pair(
+                ui,
+                "Avg SAC:",
+                &Stats::average_readable(consumption.sac, 2, "bar/min"),
+            );
+            self.render_pair(ui, "Avg RMV by phase:", "");
+            for (phase, average) in [
+                ("descent", consumption.rmv_descent),
+                ("bottom", consumption.rmv_bottom),
+                ("ascent", consumption.rmv_ascent),
+            ] {
+                ui.indent("", |ui| {
+                    self.render_pair(
+                        ui,
+                        &format!("-{phase}:"),
+                        &Stats::average_readable(average, 1, "l/min"),
+                    );
+                });
+            }
+            if !consumption.rmv_per_month.is_empty() {
+                self.render_pair(ui, "Avg RMV by month:", "");
+                for (month, average) in consumption.rmv_per_month.iter() {
+                    ui.indent("", |ui| {
+                        self.render_pair(
+                            ui,
+                            &format!("-{month}:"),
+                            &Stats::average_readable(*average, 1, "l/min"),
+                        );
+                    });
+                }
+            }
             self.render_pair(ui, "Time below:", "");
             for record in stats.time_below.iter() {
                 let (depth, time) = record;
                 ui.indent("", |ui| {
                     self.render_pair(
                         ui,
-                        &format!("-{depth}:"),
+                        &format!("-{depth}m:"),
                         &Stats::seconds_to_readable(*time),
                     );
                 });
             }
+            if !stats.sites.is_empty() {
+                self.render_pair(ui, "Sites:", "");
+                for (name, site_stats) in stats.sites.iter() {
+                    ui.indent("", |ui| {
+                        let response = self.render_pair(
+                            ui,
+                            &format!("-{name}:"),
+                            &Stats::site_stats_readable(
+                                site_stats,
+                                self.config.stats_config.temperature_unit,
+                            ),
+                        );
+                        if let Some(coordinates) = site_stats.site.coordinates_readable() {
+                            response.response.on_hover_text(coordinates);
+                        }
+                    });
+                }
+            }
+            if !stats.buddies.is_empty() {
+                self.render_pair(ui, "Buddies:", "");
+                for (name, buddy_stats) in stats.buddies.iter() {
+                    ui.indent("", |ui| {
+                        let response = self.render_pair(
+                            ui,
+                            &format!("-{name}:"),
+                            &Stats::buddy_stats_readable(buddy_stats),
+                        );
+                        if !buddy_stats.buddy.certifications.is_empty() {
+                            response
+                                .response
+                                .on_hover_text(buddy_stats.buddy.certifications.join("\n"));
+                        }
+                    });
+                }
+            }
+            if !stats.equipment.is_empty() {
+                let now = common::now_timestamp();
+                self.render_pair(ui, "Equipment:", "");
+                for (label, equipment_stats) in stats.equipment.iter() {
+                    ui.indent("", |ui| {
+                        let response = self.render_pair(
+                            ui,
+                            &format!("-{label}:"),
+                            &Stats::equipment_stats_readable(equipment_stats, now),
+                        );
+                        response
+                            .response
+                            .on_hover_text(equipment_stats.item.kind_readable());
+                        let warnings =
+                            equipment_stats.service_warnings(&self.config.stats_config, now);
+                        for warning in warnings {
+                            ui.colored_label(egui::Color32::YELLOW, format!("⚠ {warning}"));
+                        }
+                    });
+                }
+            }
+        });
+    }
+
+    fn render_dives(&mut self, ui: &mut Ui, stats: &StatsData) {
+        ui.heading("Dives");
+        ui.separator();
+        let (sort_key, descending) = self.state.dive_sort;
+        // dives sorted again only when sort or loaded dives change
+        let order_key = (sort_key, descending, stats.dives.len());
+        let order = match &self.dive_order {
+            Some((key, order)) if *key == order_key => Arc::clone(order),
+            _ => {
+                let order = Arc::new(stats.sorted_dive_indices(sort_key, descending));
+                self.dive_order = Some((order_key, Arc::clone(&order)));
+                order
+            }
+        };
+        let spacing = 16.;
+        let row_height = ui.spacing().interact_size.y;
+        let table_width = DIVE_COLUMNS.iter().map(|(_, _, width)| width).sum::<f32>()
+            + spacing * (DIVE_COLUMNS.len() - 1) as f32;
+        let unit = self.config.stats_config.temperature_unit;
+        // only visible rows rendered, header as first row
+        egui::ScrollArea::both().show_rows(ui, row_height, order.len() + 1, |ui, rows| {
+            ui.spacing_mut().item_spacing.x = spacing;
+            ui.set_min_width(table_width);
+            for row in rows {
+                let i = match row.checked_sub(1) {
+                    Some(i) => i,
+                    None => {
+                        ui.horizontal(|ui| self.render_dives_header(ui, row_height));
+                        continue;
+                    }
+                };
+                let dive = &stats.dives[order[i]];
+                if i % 2 == 1 {
+                    let row_rect = egui::Rect::from_min_size(
+                        ui.cursor().min,
+                        egui::vec2(table_width, row_height),
+                    );
+                    ui.painter()
+                        .rect_filled(row_rect, 0., ui.visuals().faint_bg_color);
+                }
+                ui.horizontal(|ui| {
+                    let dive_id = (dive.source.clone(), dive.repetition_group, dive.group_index);
+                    let selected = self.state.selected_dive.as_ref() == Some(&dive_id);
+                    let (_, _, width) = DIVE_COLUMNS[0];
+                    Self::table_cell(ui, width, row_height, |ui| {
+                        if ui
+                            .selectable_label(selected, (i + 1).to_string())
+                            .on_hover_text("Show profile")
+                            .clicked()
+                        {
+                            self.state.selected_dive = match selected {
+                                true => None,
+                                false => Some(dive_id),
+                            };
+                            self.state.tissues_time = 0;
+                        }
+                    });
+                    let cells = Self::dive_cells(dive, unit);
+                    for ((text, hover), (_, _, width)) in cells.into_iter().zip(&DIVE_COLUMNS[1..])
+                    {
+                        Self::table_cell(ui, *width, row_height, |ui| {
+                            let response = ui.add(egui::Label::new(text).truncate(true));
+                            if let Some(hover) = hover {
+                                response.on_hover_text(hover);
+                            }
+                        });
+                    }
+                });
+            }
+        });
+    }
+
+    // column labels, sort order changed by clicking sortable ones
+    fn render_dives_header(&mut self, ui: &mut Ui, row_height: f32) {
+        let (sort_key, descending) = self.state.dive_sort;
+        for (label, key, width) in DIVE_COLUMNS {
+            Self::table_cell(ui, width, row_height, |ui| match key {
+                Some(key) => {
+                    let label = match (key == sort_key, descending) {
+                        (true, false) => format!("{label} ⏶"),
+                        (true, true) => format!("{label} ⏷"),
+                        (false, _) => label.to_string(),
+                    };
+                    if ui.selectable_label(key == sort_key, label).clicked() {
+                        self.state.dive_sort = match key == sort_key {
+                            true => (key, !descending),
+                            false => (key, false),
+                        };
+                    }
+                }
+                None => {
+                    ui.strong(label);
+                }
+            });
+        }
+    }
+
+    // dives table cell of fixed width, content clipped so that columns stay aligned
+    fn table_cell(ui: &mut Ui, width: f32, height: f32, add_contents: impl FnOnce(&mut Ui)) {
+        let (rect, _) = ui.allocate_exact_size(egui::vec2(width, height), egui::Sense::hover());
+        let mut cell_ui = ui.child_ui(rect, egui::Layout::left_to_right(egui::Align::Center));
+        cell_ui.set_clip_rect(rect.intersect(ui.clip_rect()));
+        add_contents(&mut cell_ui);
+    }
+
+    // dives table row after dive position column, text with optional details on hover
+    fn dive_cells(dive: &Dive, unit: TemperatureUnit) -> Vec<(egui::RichText, Option<String>)> {
+        let info = &dive.info;
+        let (gf_low, gf_high) = dive.gradient_factors;
+        let text = |text: String| (egui::RichText::new(text), None);
+        let warning = |text: String, details: String| {
+            (
+                egui::RichText::new(format!("{text} ⚠")).color(egui::Color32::RED),
+                Some(details),
+            )
+        };
+        let buddies: Vec<String> = info.buddies.iter().map(|b| b.name_readable()).collect();
+        let time_below = dive
+            .time_below
+            .iter()
+            .filter(|(_, time)| *time > 0)
+            .map(|(depth, time)| format!("{depth}m: {}", Stats::seconds_to_readable(*time)))
+            .collect::<Vec<String>>()
+            .join(", ");
+        let ascent_rate = format!("{:.1}m/min", dive.ascent_rate_max);
+        vec![
+            text(Self::optional_readable(info.dive_number, |n| n.to_string())),
+            text(dive.date_readable()),
+            text(Stats::seconds_to_readable(dive.total_time)),
+            text(format!("{}m", dive.depth_max)),
+            text(Stats::seconds_to_readable(dive.time_in_deco)),
+            text(format!("{}%", dive.gf_99_max.round())),
+            text(format!("{}%", dive.gf_surf_max.round())),
+            (
+                egui::RichText::new(format!(
+                    "{} ({}/{})",
+                    Self::file_name(&dive.source),
+                    dive.repetition_group + 1,
+                    dive.group_index + 1
+                )),
+                Some(dive.source.clone()),
+            ),
+            (
+                egui::RichText::new(Self::optional_readable(info.site.as_ref(), |site| {
+                    site.name_readable()
+                })),
+                info.notes.clone(),
+            ),
+            text(match buddies.is_empty() {
+                true => "-".to_string(),
+                false => buddies.join(", "),
+            }),
+            (
+                egui::RichText::new(Self::optional_readable(
+                    dive.water_temperature_min(),
+                    |temperature| Stats::temperature_readable(temperature, unit),
+                )),
+                Stats::water_temperature_readable(dive, unit),
+            ),
+            text(Self::optional_readable(info.visibility, |visibility| {
+                format!("{visibility}m")
+            })),
+            text(Self::optional_readable(info.rating, |rating| {
+                format!("{rating}/10")
+            })),
+            text(
+                dive.surface_interval
+                    .map(Stats::seconds_to_readable)
+                    .unwrap_or("-".to_string()),
+            ),
+            text(format!("{gf_low}/{gf_high}")),
+            text(format!("{:.2}", dive.pp_o2_max)),
+            text(format!("{}%", dive.cns_max.round())),
+            text(dive.otu.round().to_string()),
+            match &dive.gas_consumption {
+                Some(gas_consumption) => (
+                    egui::RichText::new(format!("{:.1}l/min", gas_consumption.total.rmv)),
+                    Some(Stats::consumption_readable(gas_consumption)),
+                ),
+                None => text("-".to_string()),
+            },
+            text(match dive.ndl_min {
+                Some(ndl_min) => format!("{ndl_min}min"),
+                None => "-".to_string(),
+            }),
+            text(format!("{}min", dive.tts_max)),
+            text(format!("{:.1}m", dive.ceiling_max)),
+            match dive.fast_ascents {
+                0 => (
+                    egui::RichText::new(ascent_rate),
+                    Some(Stats::vertical_speed_readable(dive)),
+                ),
+                _ => warning(ascent_rate, Stats::vertical_speed_readable(dive)),
+            },
+            match dive.ceiling_violations.len() {
+                0 => text("-".to_string()),
+                violations_no => warning(
+                    format!("{violations_no} violation(s)"),
+                    dive.ceiling_violations
+                        .iter()
+                        .map(Stats::ceiling_violation_readable)
+                        .collect::<Vec<String>>()
+                        .join("\n"),
+                ),
+            },
+            match dive.gas_warnings.len() {
+                0 => text("-".to_string()),
+                warnings_no => warning(
+                    format!("{warnings_no} warning(s)"),
+                    dive.gas_warnings
+                        .iter()
+                        .map(Stats::gas_warning_readable)
+                        .collect::<Vec<String>>()
+                        .join("\n"),
+                ),
+            },
+            text(time_below),
+        ]
+    }
+
+    // selected dive if still in stats
+    fn selected_dive(&self, stats: &StatsData) -> Option<DiveId> {
+        let dive_id = self.state.selected_dive.as_ref()?;
+        let (source, repetition_group, group_index) = dive_id;
+        stats
+            .dives
+            .iter()
+            .any(|dive| {
+                &dive.source == source
+                    && dive.repetition_group == *repetition_group
+                    && dive.group_index == *group_index
+            })
+            .then(|| dive_id.clone())
+    }
+
+    // dive with samples analysed from file once per selection
+    fn profile_dive(&mut self, dive_id: DiveId) -> Result<Arc<Dive>, String> {
+        let cached = matches!(&self.profile, Some((profile_id, _)) if *profile_id == dive_id);
+        if !cached {
+            let (source, repetition_group, group_index) = &dive_id;
+            let dive = self
+                .stats
+                .dive_with_samples(source, *repetition_group, *group_index)
+                .map(Arc::new)
+                .map_err(|err| format!("Unable to show profile: {err}"));
+            self.profile = Some((dive_id, dive));
+        }
+        self.profile.as_ref().unwrap().1.clone()
+    }
+
+    // depth, ceiling and GF99 over time with gas switches and deco stops,
+    // depth plotted downwards as negative values
+    fn render_profile(&mut self, ui: &mut Ui, dive: &Arc<Dive>) {
+        ui.horizontal(|ui| {
+            ui.heading(format!(
+                "Profile: {} ({})",
+                dive.date_readable(),
+                Self::file_name(&dive.source)
+            ));
+            if ui.small_button("✖").clicked() {
+                self.state.selected_dive = None;
+            }
+        });
+        let unit = self.config.stats_config.temperature_unit;
+        let mut info = Stats::dive_info_readable(&dive.info, unit);
+        info.extend(Stats::dive_mode_readable(dive));
+        info.extend(Stats::water_temperature_readable(dive, unit));
+        if !info.is_empty() {
+            ui.label(info.join(", "));
+        }
+        let minutes = |time: Seconds| time as f64 / 60.;
+        let timeline = &dive.timeline;
+        let depth: PlotPoints = timeline
+            .iter()
+            .map(|point| [minutes(point.time), -point.depth])
+            .collect();
+        let ceiling: PlotPoints = timeline
+            .iter()
+            .map(|point| [minutes(point.time), -point.ceiling])
+            .collect();
+        let gf_99: PlotPoints = timeline
+            .iter()
+            .map(|point| [minutes(point.time), point.gf_99])
+            .collect();
+        let gas_switches: Vec<(f64, f64, String)> = dive
+            .gas_switches
+            .iter()
+            .map(|(time, gas)| {
+                let depth = Self::timeline_point_at(timeline, *time).map_or(0., |p| p.depth);
+                (minutes(*time), -depth, gas.to_string())
+            })
+            .collect();
+        let deco_stops: PlotPoints = dive
+            .deco_stops
+            .iter()
+            .map(|(time, depth, _)| [minutes(*time), -depth])
+            .collect();
+
+        self.state.tissues_time = self.state.tissues_time.min(dive.total_time);
+        let tissues_time = minutes(self.state.tissues_time);
+
+        // label formatters share dive samples instead of copying them every frame
+        let profile_dive = Arc::clone(dive);
+        Plot::new("dive_profile")
+            .height(260.)
+            .legend(Legend::default())
+            .link_axis("dive_profile_axis", true, false)
+            .link_cursor("dive_profile_cursor", true, false)
+            .include_y(0.)
+            .x_axis_label("min")
+            .y_axis_label("m")
+            .y_axis_formatter(|mark, _, _| format!("{}", -mark.value))
+            .label_formatter(move |_, value| {
+                Self::profile_label(&profile_dive.timeline, value, unit)
+            })
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new(depth).name("Depth"));
+                plot_ui.line(Line::new(ceiling).name("Ceiling"));
+                let gas_switch_points: PlotPoints = gas_switches
+                    .iter()
+                    .map(|(time, depth, _)| [*time, *depth])
+                    .collect();
+                plot_ui.points(
+                    Points::new(gas_switch_points)
+                        .shape(MarkerShape::Diamond)
+                        .radius(5.)
+                        .name("Gas switch"),
+                );
+                for (time, depth, gas) in gas_switches {
+                    plot_ui.text(
+                        Text::new(PlotPoint::new(time, depth), gas)
+                            .anchor(egui::Align2::LEFT_BOTTOM)
+                            .name("Gas switch"),
+                    );
+                }
+                plot_ui.points(
+                    Points::new(deco_stops)
+                        .shape(MarkerShape::Square)
+                        .radius(4.)
+                        .name("Deco stop"),
+                );
+                plot_ui.vline(VLine::new(tissues_time).name("Tissues"));
+            });
+
+        let gf_dive = Arc::clone(dive);
+        Plot::new("dive_gf_99")
+            .height(120.)
+            .legend(Legend::default())
+            .link_axis("dive_profile_axis", true, false)
+            .link_cursor("dive_profile_cursor", true, false)
+            .include_y(0.)
+            .x_axis_label("min")
+            .y_axis_label("%")
+            .label_formatter(move |_, value| Self::profile_label(&gf_dive.timeline, value, unit))
+            .show(ui, |plot_ui| {
+                plot_ui.line(Line::new(gf_99).name("GF99"));
+                plot_ui.vline(VLine::new(tissues_time).name("Tissues"));
+            });
+
+        self.render_tissues(ui, dive);
+    }
+
+    // tissues replayed only when selected dive or time changes
+    fn tissues_at(&mut self, dive: &Dive) -> Option<Arc<TissuesSnapshot>> {
+        let dive_id = (dive.source.clone(), dive.repetition_group, dive.group_index);
+        let key = (dive_id, self.state.tissues_time);
+        match &self.tissues {
+            Some((cached_key, tissues)) if *cached_key == key => tissues.clone(),
+            _ => {
+                let tissues = dive.tissues_at(self.state.tissues_time).map(Arc::new);
+                self.tissues = Some((key, tissues.clone()));
+                tissues
+            }
+        }
+    }
+
+    // compartments inert gas loading as % of M-value at selected dive time
+    fn render_tissues(&mut self, ui: &mut Ui, dive: &Dive) {
+        ui.horizontal(|ui| {
+            ui.label("Tissues at");
+            ui.add(
+                egui::Slider::new(&mut self.state.tissues_time, 0..=dive.total_time)
+                    .custom_formatter(|value, _| Stats::seconds_to_readable(value as Seconds)),
+            );
+            let previous = dive
+                .timeline
+                .iter()
+                .rev()
+                .find(|point| point.time < self.state.tissues_time);
+            if ui
+                .small_button("◀")
+                .on_hover_text("Previous sample")
+                .clicked()
+            {
+                self.state.tissues_time = previous.map_or(0, |point| point.time);
+            }
+            let next = dive
+                .timeline
+                .iter()
+                .find(|point| point.time > self.state.tissues_time);
+            if ui.small_button("▶").on_hover_text("Next sample").clicked() {
+                if let Some(next) = next {
+                    self.state.tissues_time = next.time;
+                }
+            }
         });
+        let tissues = match self.tissues_at(dive) {
+            Some(tissues) => tissues,
+            None => return,
+        };
+        ui.label(format!(
+            "{} at {}m, ambient pressure {:.2} bar",
+            Stats::seconds_to_readable(tissues.time),
+            tissues.depth,
+            tissues.ambient_pressure
+        ));
+
+        let percent_of_m_value = |pressure: f64, m_value: f64| match m_value > 0. {
+            true => pressure / m_value * 100.,
+            false => 0.,
+        };
+        let n2_bars: Vec<Bar> = tissues
+            .compartments
+            .iter()
+            .map(|compartment| {
+                let n2 = percent_of_m_value(compartment.n2_ip, compartment.m_value_raw);
+                Bar::new(compartment.no as f64, n2).name(format!("N2 {}", compartment.no))
+            })
+            .collect();
+        let he_bars: Vec<Bar> = tissues
+            .compartments
+            .iter()
+            .map(|compartment| {
+                let he = percent_of_m_value(compartment.he_ip, compartment.m_value_raw);
+                Bar::new(compartment.no as f64, he).name(format!("He {}", compartment.no))
+            })
+            .collect();
+        let gf_m_values: PlotPoints = tissues
+            .compartments
+            .iter()
+            .map(|compartment| {
+                let gf_m_value =
+                    percent_of_m_value(compartment.m_value_calc, compartment.m_value_raw);
+                [compartment.no as f64, gf_m_value]
+            })
+            .collect();
+        let ambient: PlotPoints = tissues
+            .compartments
+            .iter()
+            .map(|compartment| {
+                let ambient = percent_of_m_value(tissues.ambient_pressure, compartment.m_value_raw);
+                [compartment.no as f64, ambient]
+            })
+            .collect();
+
+        Plot::new("dive_tissues")
+            .height(220.)
+            .legend(Legend::default())
+            .include_y(0.)
+            .include_y(100.)
+            .x_axis_label("compartment")
+            .y_axis_label("% of M-value")
+            .allow_drag(false)
+            .allow_zoom(false)
+            .allow_scroll(false)
+            .show(ui, |plot_ui| {
+                let n2_chart = BarChart::new(n2_bars).name("N2").width(0.7);
+                let he_chart = BarChart::new(he_bars)
+                    .name("He")
+                    .width(0.7)
+                    .stack_on(&[&n2_chart]);
+                plot_ui.bar_chart(n2_chart);
+                plot_ui.bar_chart(he_chart);
+                plot_ui.hline(HLine::new(100.).name("M-value"));
+                plot_ui.points(
+                    Points::new(gf_m_values)
+                        .shape(MarkerShape::Diamond)
+                        .radius(4.)
+                        .name("GF M-value"),
+                );
+                plot_ui.points(
+                    Points::new(ambient)
+                        .shape(MarkerShape::Circle)
+                        .radius(3.)
+                        .name("Ambient pressure"),
+                );
+            });
+    }
+
+    // values at sample closest to hovered time
+    fn profile_label(
+        timeline: &[TimelinePoint],
+        value: &PlotPoint,
+        unit: TemperatureUnit,
+    ) -> String {
+        let time = (value.x.max(0.) * 60.).round() as Seconds;
+        let point = match Self::timeline_point_at(timeline, time) {
+            Some(point) => point,
+            None => return String::new(),
+        };
+        let optional_minutes = |value: Option<dive_deco::Minutes>| match value {
+            Some(value) => format!("{value}min"),
+            None => "-".to_string(),
+        };
+        let mut label = format!(
+            "{}\nDepth: {}m\nCeiling: {:.1}m\nNDL: {}\nTTS: {}\nGF99: {:.0}%\nppO2: {:.2}",
+            Stats::seconds_to_readable(point.time),
+            point.depth,
+            point.ceiling,
+            optional_minutes(point.ndl),
+            optional_minutes(point.tts),
+            point.gf_99,
+            point.pp_o2
+        );
+        if let Some(temperature) = point.temperature {
+            label.push_str(&format!(
+                "\nTemperature: {}",
+                Stats::temperature_readable(temperature, unit)
+            ));
+        }
+        label
+    }
+
+    // closest sample to dive time
+    fn timeline_point_at(timeline: &[TimelinePoint], time: Seconds) -> Option<&TimelinePoint> {
+        let position = timeline.partition_point(|point| point.time < time);
+        let next = timeline.get(position);
+        let previous = position.checked_sub(1).and_then(|i| timeline.get(i));
+        match (previous, next) {
+            (Some(previous), Some(next)) if time - previous.time < next.time - time => {
+                Some(previous)
+            }
+            (_, Some(next)) => Some(next),
+            (previous, None) => previous,
+        }
+    }
+
+    fn optional_readable<T>(value: Option<T>, readable: impl Fn(T) -> String) -> String {
+        value.map(readable).unwrap_or("-".to_string())
+    }
+
+    fn file_name(path: &str) -> String {
+        PathBuf::from(path)
+            .file_name()
+            .map(|name| name.to_string_lossy().to_string())
+            .unwrap_or(path.to_string())
     }
 
     pub fn render_pair(&self, ui: &mut Ui, v1: &str, v2: &str) -> InnerResponse<()> {
@@ -171,6 +1205,28 @@ impl App {
         })
     }
 
+    fn render_skipped(&self, ui: &mut Ui, stats: &StatsData) {
+        let header = format!("⚠ Skipped ({})", stats.skipped.len());
+        egui::CollapsingHeader::new(header)
+            .default_open(true)
+            .show(ui, |ui| {
+                for skipped in stats.skipped.iter() {
+                    ui.label(skipped.to_string());
+                }
+            });
+    }
+
+    fn render_warnings(&self, ui: &mut Ui, warnings: &[String]) {
+        let header = format!("⚠ Configured values used ({})", warnings.len());
+        egui::CollapsingHeader::new(header)
+            .default_open(true)
+            .show(ui, |ui| {
+                for warning in warnings.iter() {
+                    ui.label(warning);
+                }
+            });
+    }
+
     fn render_error(&self, ui: &mut Ui, err: &AppError) {
         let err_details_text = err.text.to_string();
 
@@ -180,27 +1236,107 @@ impl App {
         });
     }
 
-    fn update_path(&mut self, new_path: String) {
-        self.config.path = Some(new_path);
-    }
-
-    fn run_stats(&mut self, file_path: &PathBuf) {
-        let selected_path = file_path.to_str().unwrap();
-        self.update_path(selected_path.to_string());
-        let stats_res = Stats::new().from_path(file_path.to_str().unwrap());
-        match stats_res {
-            Ok(stats) => {
-                if let Some(err) = &self.state.error {
-                    self.state.error = None;
+    fn render_loading(&mut self, ui: &mut Ui) {
+        let progress = Arc::clone(&self.stats.progress);
+        let (files_total, files_parsed) = (progress.files_total(), progress.files_parsed());
+        let progress_text = format!(
+            "{files_parsed}/{files_total} files parsed, {} dives analysed",
+            progress.dives_analysed()
+        );
+        if self.loading.is_some() {
+            ui.add(egui::ProgressBar::new(progress.fraction()).text(progress_text));
+            match progress.is_cancelled() {
+                true => {
+                    ui.label("Cancelling..");
+                }
+                false => {
+                    if ui.button("Cancel").clicked() {
+                        progress.cancel();
+                    }
                 }
-                self.stats = stats
             }
-            Err(err) => {
-                let app_err = AppError {
-                    text: err.to_string(),
-                };
-                self.state.error = Some(app_err);
+        } else if self.state.incomplete {
+            ui.label(format!(
+                "⚠ Loading cancelled, stats incomplete ({progress_text})"
+            ));
+        }
+    }
+
+    // loading state update, repaint scheduled for incremental stats while running
+    fn poll_loading(&mut self, ctx: &egui::Context) {
+        let finished = match &self.loading {
+            Some(loading) => loading.is_finished(),
+            None => return,
+        };
+        if !finished {
+            ctx.request_repaint_after(Duration::from_millis(100));
+            return;
+        }
+        let result = match self.loading.take().map(JoinHandle::join) {
+            Some(Ok(result)) => result,
+            Some(Err(_)) => Err("Analysis stopped unexpectedly".to_string()),
+            None => Ok(()),
+        };
+        if let Err(text) = result {
+            self.state.error = Some(AppError { text });
+        }
+        if self.stats.progress.is_cancelled() {
+            self.state.incomplete = true;
+        }
+    }
+
+    // sources not covered by already added ones analysed into current stats
+    fn add_sources(&mut self, paths: Vec<PathBuf>) {
+        let mut added: Vec<String> = vec![];
+        for path in paths {
+            let covered = self
+                .config
+                .sources
+                .iter()
+                .any(|source| path.starts_with(source));
+            if !covered {
+                let source = path.to_string_lossy().to_string();
+                self.config.sources.push(source.clone());
+                added.push(source);
             }
         }
+        if !added.is_empty() {
+            self.run_stats(added);
+        }
+    }
+
+    // dives only from the removed source dropped, without analysing remaining ones
+    fn remove_source(&mut self, i: usize) {
+        let removed = self.config.sources.remove(i);
+        let sources = self.config.sources.clone();
+        self.stats.remove_sources(|source| {
+            let source = Path::new(source);
+            source.starts_with(&removed) && !sources.iter().any(|s| source.starts_with(s))
+        });
+        self.state.error = None;
+        if sources.is_empty() {
+            self.state.incomplete = false;
+        }
+    }
+
+    // all sources analysed again with current config
+    fn reload_sources(&mut self) {
+        // previous analysis results no longer needed
+        self.stats.progress.cancel();
+        self.stats = Stats::with_config(self.config.stats_config.clone());
+        self.state.incomplete = false;
+        // profile, dives order and tissues calculated again with current config
+        self.profile = None;
+        self.dive_order = None;
+        self.tissues = None;
+        self.run_stats(self.config.sources.clone());
+    }
+
+    fn run_stats(&mut self, paths: Vec<String>) {
+        let stats = self.stats.clone();
+        self.state.error = None;
+        self.loading = Some(thread::spawn(move || {
+            stats.load_paths(&paths).map_err(|err| err.to_string())
+        }));
     }
 }
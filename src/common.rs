@@ -2,3 +2,270 @@ pub type Depth = f64;
 pub type Seconds = u64;
 pub type GF = f64;
 pub type GradientFactorsSetting = (u8, u8);
+pub type Timestamp = i64;
+// meters per minute
+pub type VerticalSpeed = f64;
+// degrees Celsius
+pub type Temperature = f64;
+
+// seconds since epoch from ISO 8601 date time (eg. 2023-06-01T09:30:00), timezone ignored
+pub fn parse_timestamp(date_time: &str) -> Option<Timestamp> {
+    let (date, time) = match date_time.trim().split_once(['T', ' ']) {
+        Some((date, time)) => (date, time),
+        None => (date_time.trim(), ""),
+    };
+    let mut date_parts = date.split('-').map(|part| part.parse::<i64>().ok());
+    let year = date_parts.next()??;
+    let month = date_parts.next()??;
+    let day = date_parts.next()??;
+    if !(1..=12).contains(&month) || !(1..=days_in_month(year, month)).contains(&day) {
+        return None;
+    }
+    let time: String = time
+        .chars()
+        .take_while(|c| c.is_ascii_digit() || *c == ':')
+        .collect();
+    let mut time_parts = time.split(':').map(|part| part.parse::<i64>().unwrap_or(0));
+    let hours = time_parts.next().unwrap_or(0);
+    let minutes = time_parts.next().unwrap_or(0);
+    let seconds = time_parts.next().unwrap_or(0);
+
+    Some(days_from_civil(year, month, day) * 86400 + hours * 3600 + minutes * 60 + seconds)
+}
+
+// current time as seconds since epoch
+pub fn now_timestamp() -> Timestamp {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|duration| duration.as_secs() as Timestamp)
+        .unwrap_or(0)
+}
+
+// date as "YYYY-MM-DD" from seconds since epoch
+pub fn timestamp_to_date(timestamp: Timestamp) -> String {
+    let (year, month, day) = civil_from_days(timestamp.div_euclid(86400));
+    format!("{year:04}-{month:02}-{day:02}")
+}
+
+// month length in Gregorian calendar, February 29 days in leap years
+fn days_in_month(year: i64, month: i64) -> i64 {
+    match month {
+        2 if year % 4 == 0 && (year % 100 != 0 || year % 400 == 0) => 29,
+        2 => 28,
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+// days since 1970-01-01 in proleptic Gregorian calendar
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month_index = (month + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+// year, month and day from days since 1970-01-01
+fn civil_from_days(days: i64) -> (i64, i64, i64) {
+    let days = days + 719468;
+    let era = if days >= 0 { days } else { days - 146096 } / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * month_index + 2) / 5 + 1;
+    let month = if month_index < 10 {
+        month_index + 3
+    } else {
+        month_index - 9
+    };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    (year, month, day)
+}
+
+pub type MbarPressure = u16;
+pub type Density = f64;
+
+pub const DEFAULT_SURFACE_PRESSURE: MbarPressure = 1013;
+pub const SALT_WATER_DENSITY: Density = 1025.;
+pub const FRESH_WATER_DENSITY: Density = 1000.;
+const GRAVITY: f64 = 9.80665;
+
+// standard atmosphere surface pressure at altitude (meters)
+pub fn surface_pressure_at_altitude(altitude: f64) -> MbarPressure {
+    let pressure = 1013.25 * (1. - 2.25577e-5 * altitude).powf(5.25588);
+    pressure.round() as MbarPressure
+}
+
+// hydrostatic pressure in bar per meter of water with given density (kg/m3)
+pub fn pressure_per_meter(water_density: Density) -> f64 {
+    water_density * GRAVITY / 100_000.
+}
+
+// Celsius from Kelvin used in UDDF, rounded to hundredths
+pub fn kelvin_to_celsius(kelvin: f64) -> Temperature {
+    ((kelvin - 273.15) * 100.).round() / 100.
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum TemperatureUnit {
+    #[default]
+    Celsius,
+    Fahrenheit,
+}
+
+impl TemperatureUnit {
+    pub fn value_from_celsius(&self, temperature: Temperature) -> f64 {
+        match self {
+            Self::Celsius => temperature,
+            Self::Fahrenheit => temperature * 9. / 5. + 32.,
+        }
+    }
+
+    // temperature difference, without scale offset
+    pub fn difference_from_celsius(&self, difference: Temperature) -> f64 {
+        match self {
+            Self::Celsius => difference,
+            Self::Fahrenheit => difference * 9. / 5.,
+        }
+    }
+
+    pub fn symbol(&self) -> &'static str {
+        match self {
+            Self::Celsius => "°C",
+            Self::Fahrenheit => "°F",
+        }
+    }
+
+    // suffix for output keys
+    pub fn key(&self) -> &'static str {
+        match self {
+            Self::Celsius => "c",
+            Self::Fahrenheit => "f",
+        }
+    }
+}
+
+// running average of values added one by one
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Average {
+    sum: f64,
+    pub count: usize,
+}
+
+impl Average {
+    pub fn add(&mut self, value: f64) {
+        self.sum += value;
+        self.count += 1;
+    }
+
+    pub fn value(&self) -> Option<f64> {
+        match self.count {
+            0 => None,
+            count => Some(self.sum / count as f64),
+        }
+    }
+
+    pub fn merge(&mut self, other: Average) {
+        self.sum += other.sum;
+        self.count += other.count;
+    }
+}
+
+// value added to entry with equal key, or inserted as new entry keeping entries sorted by key
+pub fn add_sorted<K, V>(
+    entries: &mut Vec<(K, V)>,
+    key: K,
+    value: V,
+    cmp: impl Fn(&K, &K) -> std::cmp::Ordering,
+    add: impl FnOnce(&mut V, V),
+) {
+    match entries.binary_search_by(|(entry_key, _)| cmp(entry_key, &key)) {
+        Ok(i) => add(&mut entries[i].1, value),
+        Err(i) => entries.insert(i, (key, value)),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_timestamp() {
+        assert_eq!(parse_timestamp("1970-01-01T00:00:00"), Some(0));
+        assert_eq!(parse_timestamp("2023-06-01T09:30:15"), Some(1685611815));
+        assert_eq!(parse_timestamp("2023-06-01 09:30"), Some(1685611800));
+        // date only, midnight
+        assert_eq!(parse_timestamp("2023-06-01"), Some(1685577600));
+        // timezone and fractional seconds ignored
+        assert_eq!(
+            parse_timestamp("2023-06-01T09:30:15+02:00"),
+            Some(1685611815)
+        );
+        assert_eq!(parse_timestamp("2023-06-01T09:30:15.5Z"), Some(1685611815));
+        assert_eq!(parse_timestamp("1969-12-31T00:00:00"), Some(-86400));
+    }
+
+    #[test]
+    fn rejects_malformed_timestamp() {
+        assert_eq!(parse_timestamp(""), None);
+        assert_eq!(parse_timestamp("yesterday"), None);
+        assert_eq!(parse_timestamp("2023-06"), None);
+        assert_eq!(parse_timestamp("2023-13-01T10:00:00"), None);
+        assert_eq!(parse_timestamp("2023-00-01"), None);
+        assert_eq!(parse_timestamp("2023-06-32"), None);
+        assert_eq!(parse_timestamp("2023-06-xx"), None);
+    }
+
+    #[test]
+    fn rejects_day_beyond_month_length() {
+        assert_eq!(parse_timestamp("2023-02-31T10:00:00"), None);
+        assert_eq!(parse_timestamp("2023-02-29"), None);
+        assert_eq!(parse_timestamp("2023-04-31"), None);
+        assert_eq!(parse_timestamp("1900-02-29"), None);
+        // leap years
+        assert!(parse_timestamp("2024-02-29").is_some());
+        assert!(parse_timestamp("2000-02-29").is_some());
+        assert!(parse_timestamp("2023-01-31").is_some());
+        assert!(parse_timestamp("2023-04-30").is_some());
+    }
+
+    #[test]
+    fn converts_days_to_civil_and_back() {
+        let dates = [
+            (1970, 1, 1),
+            (1969, 12, 31),
+            (2000, 2, 29),
+            (2023, 12, 31),
+            (2024, 2, 29),
+            (2024, 3, 1),
+            (2100, 3, 1),
+            (1600, 1, 1),
+        ];
+        for (year, month, day) in dates {
+            let days = days_from_civil(year, month, day);
+            assert_eq!(civil_from_days(days), (year, month, day));
+        }
+        assert_eq!(days_from_civil(1970, 1, 1), 0);
+        assert_eq!(
+            days_from_civil(2024, 3, 1) - days_from_civil(2024, 2, 28),
+            2
+        );
+        assert_eq!(
+            days_from_civil(2023, 3, 1) - days_from_civil(2023, 2, 28),
+            1
+        );
+    }
+
+    #[test]
+    fn formats_timestamp_as_date() {
+        assert_eq!(timestamp_to_date(0), "1970-01-01");
+        assert_eq!(timestamp_to_date(1685611815), "2023-06-01");
+        assert_eq!(timestamp_to_date(-1), "1969-12-31");
+        assert_eq!(timestamp_to_date(951782400), "2000-02-29");
+    }
+}
@@ -0,0 +1,514 @@
+use dive_deco::Pressure;
+
+use crate::common::{self, Average, Depth, Seconds};
+use crate::parser::{TankDataElem, WaypointElem};
+
+// liters
+pub type Volume = f64;
+// surface air consumption, bar/min
+pub type Sac = f64;
+// respiratory minute volume, l/min at surface pressure
+pub type Rmv = f64;
+
+// bottom phase between first and last sample at or below this fraction of max depth
+const BOTTOM_DEPTH_FRACTION: f64 = 2. / 3.;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Consumption {
+    // consumed gas at surface pressure
+    pub volume: Volume,
+    pub rmv: Rmv,
+    // only if gas breathed from a single tank
+    pub sac: Option<Sac>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TankConsumption {
+    pub id: Option<String>,
+    // linked gas mix id
+    pub mix: Option<String>,
+    pub tank_volume: Volume,
+    // bar
+    pub pressure_begin: Pressure,
+    pub pressure_end: Pressure,
+    // between first and last tank pressure sample, or whole dive without samples
+    pub consumption: Consumption,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GasConsumption {
+    pub tanks: Vec<TankConsumption>,
+    pub total: Consumption,
+    // by dive phase, only with tank pressure samples
+    pub descent: Option<Consumption>,
+    pub bottom: Option<Consumption>,
+    pub ascent: Option<Consumption>,
+}
+
+// time (seconds) and value samples
+type Samples = Vec<(Seconds, f64)>;
+
+// consumed gas from tank data and tank pressure samples,
+// normalized to surface with ambient pressure (bar) at depth
+pub fn calc_gas_consumption(
+    waypoints: &[WaypointElem],
+    tank_data: &[TankDataElem],
+    ambient_pressure: impl Fn(Depth) -> Pressure,
+) -> Option<GasConsumption> {
+    let ambient_samples: Samples = waypoints
+        .iter()
+        .map(|waypoint| (waypoint.dive_time, ambient_pressure(waypoint.depth)))
+        .collect();
+    let dive_end = ambient_samples.last()?.0;
+
+    let mut tanks = vec![];
+    let mut tank_samples = vec![];
+    for tank in tank_data {
+        let samples = tank_pressure_samples(waypoints, tank, tank_data.len());
+        if let Some(tank_consumption) = tank_consumption(tank, &samples, &ambient_samples, dive_end)
+        {
+            if samples.len() >= 2 {
+                tank_samples.push((tank_consumption.tank_volume, samples));
+            }
+            tanks.push(tank_consumption);
+        }
+    }
+    if tanks.is_empty() {
+        return None;
+    }
+
+    let single_tank_volume = match tanks.len() {
+        1 => Some(tanks[0].tank_volume),
+        _ => None,
+    };
+    let volume = tanks.iter().map(|tank| tank.consumption.volume).sum();
+    let total = consumption(
+        volume,
+        pressure_time(&ambient_samples, 0, dive_end),
+        single_tank_volume,
+    )?;
+
+    // phases only if every consumed tank has pressure samples
+    let phase_consumption = |start: Seconds, end: Seconds| {
+        if tank_samples.len() != tanks.len() {
+            return None;
+        }
+        let volume = tank_samples
+            .iter()
+            .map(|(tank_volume, samples)| {
+                tank_volume * (interpolate(samples, start) - interpolate(samples, end))
+            })
+            .sum();
+        consumption(
+            volume,
+            pressure_time(&ambient_samples, start, end),
+            single_tank_volume,
+        )
+    };
+    let (descent, bottom, ascent) = match bottom_phase(waypoints) {
+        Some((bottom_start, bottom_end)) => (
+            phase_consumption(0, bottom_start),
+            phase_consumption(bottom_start, bottom_end),
+            phase_consumption(bottom_end, dive_end),
+        ),
+        None => (None, None, None),
+    };
+
+    Some(GasConsumption {
+        tanks,
+        total,
+        descent,
+        bottom,
+        ascent,
+    })
+}
+
+fn tank_consumption(
+    tank: &TankDataElem,
+    samples: &Samples,
+    ambient_samples: &Samples,
+    dive_end: Seconds,
+) -> Option<TankConsumption> {
+    let tank_volume = tank.tank_volume? * 1000.;
+    if tank_volume.is_nan() || tank_volume <= 0. {
+        return None;
+    }
+    let (start, pressure_begin, end, pressure_end) = match (samples.first(), samples.last()) {
+        (Some(first), Some(last)) if samples.len() >= 2 => (first.0, first.1, last.0, last.1),
+        _ => (
+            0,
+            tank.tank_pressure_begin? / 100_000.,
+            dive_end,
+            tank.tank_pressure_end? / 100_000.,
+        ),
+    };
+    if pressure_begin.is_nan() || pressure_end.is_nan() || pressure_begin < pressure_end {
+        return None;
+    }
+    let volume = tank_volume * (pressure_begin - pressure_end);
+    let consumption = consumption(
+        volume,
+        pressure_time(ambient_samples, start, end),
+        Some(tank_volume),
+    )?;
+
+    Some(TankConsumption {
+        id: tank.id.clone(),
+        mix: tank
+            .links
+            .as_ref()
+            .and_then(|links| links.first())
+            .map(|link| link.link_ref.clone()),
+        tank_volume,
+        pressure_begin,
+        pressure_end,
+        consumption,
+    })
+}
+
+// tank pressure samples (bar) referencing the tank, unreferenced ones if only one tank used
+fn tank_pressure_samples(
+    waypoints: &[WaypointElem],
+    tank: &TankDataElem,
+    tanks_no: usize,
+) -> Samples {
+    let mut samples = vec![];
+    for waypoint in waypoints {
+        let tank_pressures = waypoint.tank_pressures.as_deref().unwrap_or_default();
+        for tank_pressure in tank_pressures {
+            let matches_tank = match &tank_pressure.tank_ref {
+                Some(tank_ref) => Some(tank_ref) == tank.id.as_ref(),
+                None => tanks_no == 1,
+            };
+            if matches_tank {
+                samples.push((waypoint.dive_time, tank_pressure.value / 100_000.));
+            }
+        }
+    }
+    samples
+}
+
+// consumption from surface volume and pressure-time (bar * min)
+fn consumption(
+    volume: Volume,
+    pressure_time: f64,
+    tank_volume: Option<Volume>,
+) -> Option<Consumption> {
+    if pressure_time.is_nan() || pressure_time <= 0. {
+        return None;
+    }
+    let rmv = volume / pressure_time;
+    Some(Consumption {
+        volume,
+        rmv,
+        sac: tank_volume.map(|tank_volume| rmv / tank_volume),
+    })
+}
+
+// start and end time of bottom phase
+fn bottom_phase(waypoints: &[WaypointElem]) -> Option<(Seconds, Seconds)> {
+    let depth_max = waypoints
+        .iter()
+        .map(|waypoint| waypoint.depth)
+        .fold(0., Depth::max);
+    if depth_max <= 0. {
+        return None;
+    }
+    let bottom_depth = depth_max * BOTTOM_DEPTH_FRACTION;
+    let mut bottom_waypoints = waypoints
+        .iter()
+        .filter(|waypoint| waypoint.depth >= bottom_depth);
+    let start = bottom_waypoints.next()?.dive_time;
+    let end = bottom_waypoints
+        .next_back()
+        .map_or(start, |waypoint| waypoint.dive_time);
+    Some((start, end))
+}
+
+// linearly interpolated value at given time, clamped to samples range
+fn interpolate(samples: &Samples, time: Seconds) -> f64 {
+    let position = samples.partition_point(|(sample_time, _)| *sample_time <= time);
+    let previous = position.checked_sub(1).map(|i| samples[i]);
+    let next = samples.get(position).copied();
+    match (previous, next) {
+        (Some((t0, v0)), Some((t1, v1))) if t1 > t0 => {
+            v0 + (v1 - v0) * (time - t0) as f64 / (t1 - t0) as f64
+        }
+        (Some((_, v0)), _) => v0,
+        (None, Some((_, v1))) => v1,
+        (None, None) => 0.,
+    }
+}
+
+// integral of ambient pressure over time between start and end, bar * min
+fn pressure_time(ambient_samples: &Samples, start: Seconds, end: Seconds) -> f64 {
+    let mut pressure_time = 0.;
+    for segment in ambient_samples.windows(2) {
+        let segment_start = segment[0].0.max(start);
+        let segment_end = segment[1].0.min(end);
+        if segment_end <= segment_start {
+            continue;
+        }
+        let pressure_start = interpolate(ambient_samples, segment_start);
+        let pressure_end = interpolate(ambient_samples, segment_end);
+        let minutes = (segment_end - segment_start) as f64 / 60.;
+        pressure_time += (pressure_start + pressure_end) / 2. * minutes;
+    }
+    pressure_time
+}
+
+// logbook averages, SAC only from single tank dives
+#[derive(Clone, Debug, Default)]
+pub struct ConsumptionStats {
+    pub rmv: Average,
+    pub sac: Average,
+    pub rmv_descent: Average,
+    pub rmv_bottom: Average,
+    pub rmv_ascent: Average,
+    // RMV by month ("YYYY-MM"), kept sorted by month, dives without date not included
+    pub rmv_per_month: Vec<(String, Average)>,
+}
+
+impl ConsumptionStats {
+    pub fn add(&mut self, gas_consumption: &GasConsumption, date: Option<&str>) {
+        let GasConsumption {
+            total,
+            descent,
+            bottom,
+            ascent,
+            ..
+        } = gas_consumption;
+        self.rmv.add(total.rmv);
+        if let Some(sac) = total.sac {
+            self.sac.add(sac);
+        }
+        for (average, phase) in [
+            (&mut self.rmv_descent, descent),
+            (&mut self.rmv_bottom, bottom),
+            (&mut self.rmv_ascent, ascent),
+        ] {
+            if let Some(phase) = phase {
+                average.add(phase.rmv);
+            }
+        }
+        if let Some(month) = date.and_then(|date| date.get(..7)) {
+            let mut rmv = Average::default();
+            rmv.add(total.rmv);
+            common::add_sorted(
+                &mut self.rmv_per_month,
+                month.to_string(),
+                rmv,
+                String::cmp,
+                Average::merge,
+            );
+        }
+    }
+
+    pub fn merge(&mut self, other: ConsumptionStats) {
+        self.rmv.merge(other.rmv);
+        self.sac.merge(other.sac);
+        self.rmv_descent.merge(other.rmv_descent);
+        self.rmv_bottom.merge(other.rmv_bottom);
+        self.rmv_ascent.merge(other.rmv_ascent);
+        for (month, rmv) in other.rmv_per_month {
+            common::add_sorted(
+                &mut self.rmv_per_month,
+                month,
+                rmv,
+                String::cmp,
+                Average::merge,
+            );
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // tank ref, bar
+    type TankPressure<'a> = (Option<&'a str>, f64);
+    // dive time, depth, tank pressures
+    type Sample<'a> = (Seconds, Depth, &'a [TankPressure<'a>]);
+
+    fn waypoints(samples: &[Sample]) -> Vec<WaypointElem> {
+        samples
+            .iter()
+            .map(|(time, depth, tank_pressures)| {
+                let tank_pressures: String = tank_pressures
+                    .iter()
+                    .map(|(tank_ref, pressure)| {
+                        let tank_ref = tank_ref.map_or(String::new(), |r| format!(" ref=\"{r}\""));
+                        format!("<tankpressure{tank_ref}>{}</tankpressure>", pressure * 100_000.)
+                    })
+                    .collect();
+                let xml = format!(
+                    "<waypoint><divetime>{time}</divetime><depth>{depth}</depth>{tank_pressures}</waypoint>"
+                );
+                quick_xml::de::from_str(&xml).unwrap()
+            })
+            .collect()
+    }
+
+    // liters, bar
+    fn tank(id: &str, volume: Volume, pressure_begin: f64, pressure_end: f64) -> TankDataElem {
+        TankDataElem {
+            id: Some(id.to_string()),
+            links: None,
+            tank_volume: Some(volume / 1000.),
+            tank_pressure_begin: Some(pressure_begin * 100_000.),
+            tank_pressure_end: Some(pressure_end * 100_000.),
+        }
+    }
+
+    fn ambient_pressure(depth: Depth) -> Pressure {
+        1. + depth / 10.
+    }
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!(
+            (value - expected).abs() < 1e-9,
+            "{value} not equal to {expected}"
+        );
+    }
+
+    // 20m for 20 minutes with 1 minute descent and ascent, 64 bar * min
+    const SQUARE_PROFILE: [(Seconds, Depth); 4] = [(0, 0.), (60, 20.), (1260, 20.), (1320, 0.)];
+
+    #[test]
+    fn calculates_rmv_and_sac_from_tank_data() {
+        let waypoints = waypoints(&SQUARE_PROFILE.map(|(time, depth)| (time, depth, &[][..])));
+        let gas_consumption =
+            calc_gas_consumption(&waypoints, &[tank("t1", 12., 200., 72.)], ambient_pressure)
+                .unwrap();
+        let total = gas_consumption.total;
+        assert_close(total.volume, 1536.);
+        assert_close(total.rmv, 24.);
+        // bar/min, RMV divided by tank volume
+        assert_close(total.sac.unwrap(), 2.);
+        assert_eq!(gas_consumption.tanks.len(), 1);
+        assert_eq!(gas_consumption.tanks[0].consumption, total);
+        // no phases without pressure samples
+        assert_eq!(gas_consumption.descent, None);
+        assert_eq!(gas_consumption.bottom, None);
+        assert_eq!(gas_consumption.ascent, None);
+    }
+
+    #[test]
+    fn calculates_phases_from_tank_pressure_samples() {
+        let pressures = [200., 196., 76., 72.];
+        let samples: Vec<(Seconds, Depth, [TankPressure; 1])> = SQUARE_PROFILE
+            .iter()
+            .zip(pressures)
+            .map(|((time, depth), pressure)| (*time, *depth, [(None, pressure)]))
+            .collect();
+        let samples: Vec<Sample> = samples
+            .iter()
+            .map(|(time, depth, pressures)| (*time, *depth, &pressures[..]))
+            .collect();
+        // tank data pressures ignored with samples
+        let gas_consumption = calc_gas_consumption(
+            &waypoints(&samples),
+            &[tank("t1", 12., 230., 50.)],
+            ambient_pressure,
+        )
+        .unwrap();
+        assert_close(gas_consumption.total.rmv, 24.);
+        for phase in [
+            gas_consumption.descent,
+            gas_consumption.bottom,
+            gas_consumption.ascent,
+        ] {
+            let phase = phase.unwrap();
+            assert_close(phase.rmv, 24.);
+            assert_close(phase.sac.unwrap(), 2.);
+        }
+        assert_close(gas_consumption.descent.unwrap().volume, 48.);
+        assert_close(gas_consumption.bottom.unwrap().volume, 1440.);
+    }
+
+    #[test]
+    fn calculates_rmv_without_sac_for_multiple_tanks() {
+        let waypoints = waypoints(&SQUARE_PROFILE.map(|(time, depth)| (time, depth, &[][..])));
+        let tanks = [
+            tank("back", 24., 200., 136.),
+            tank("stage", 11., 200., 200.),
+        ];
+        let gas_consumption = calc_gas_consumption(&waypoints, &tanks, ambient_pressure).unwrap();
+        assert_close(gas_consumption.total.rmv, 24.);
+        assert_eq!(gas_consumption.total.sac, None);
+        // per tank SAC kept
+        assert_close(gas_consumption.tanks[0].consumption.sac.unwrap(), 1.);
+        assert_close(gas_consumption.tanks[1].consumption.rmv, 0.);
+    }
+
+    #[test]
+    fn skips_invalid_tank_data() {
+        let waypoints = waypoints(&SQUARE_PROFILE.map(|(time, depth)| (time, depth, &[][..])));
+        let filled = tank("t1", 12., 50., 200.);
+        let mut no_volume = tank("t1", 12., 200., 50.);
+        no_volume.tank_volume = None;
+        let mut no_pressure = tank("t1", 12., 200., 50.);
+        no_pressure.tank_pressure_end = None;
+        for tank in [filled, no_volume, no_pressure] {
+            assert_eq!(
+                calc_gas_consumption(&waypoints, &[tank], ambient_pressure),
+                None
+            );
+        }
+        assert_eq!(
+            calc_gas_consumption(&[], &[tank("t1", 12., 200., 50.)], ambient_pressure),
+            None
+        );
+    }
+
+    #[test]
+    fn finds_bottom_phase_below_two_thirds_of_max_depth() {
+        let bottom = |profile: &[(Seconds, Depth)]| {
+            let samples: Vec<Sample> = profile
+                .iter()
+                .map(|(time, depth)| (*time, *depth, &[][..]))
+                .collect();
+            bottom_phase(&waypoints(&samples))
+        };
+        assert_eq!(bottom(&SQUARE_PROFILE), Some((60, 1260)));
+        // 2/3 of 30m is 20m
+        assert_eq!(
+            bottom(&[
+                (0, 0.),
+                (60, 19.9),
+                (120, 20.),
+                (180, 30.),
+                (240, 10.),
+                (300, 20.),
+                (360, 0.)
+            ]),
+            Some((120, 300))
+        );
+        assert_eq!(bottom(&[(0, 0.), (60, 12.), (120, 0.)]), Some((60, 60)));
+        assert_eq!(bottom(&[(0, 0.), (60, 0.)]), None);
+        assert_eq!(bottom(&[]), None);
+    }
+
+    #[test]
+    fn interpolates_clamped_to_samples() {
+        let samples = vec![(60, 10.), (120, 20.)];
+        assert_close(interpolate(&samples, 0), 10.);
+        assert_close(interpolate(&samples, 60), 10.);
+        assert_close(interpolate(&samples, 90), 15.);
+        assert_close(interpolate(&samples, 120), 20.);
+        assert_close(interpolate(&samples, 600), 20.);
+        assert_close(interpolate(&vec![(60, 5.)], 0), 5.);
+        assert_close(interpolate(&vec![], 60), 0.);
+    }
+
+    #[test]
+    fn integrates_pressure_time_between_times() {
+        let ambient_samples = vec![(0, 1.), (60, 3.), (120, 3.)];
+        assert_close(pressure_time(&ambient_samples, 0, 120), 5.);
+        assert_close(pressure_time(&ambient_samples, 30, 90), 2.75);
+        // clamped to samples range
+        assert_close(pressure_time(&ambient_samples, 0, 600), 5.);
+        assert_close(pressure_time(&ambient_samples, 90, 30), 0.);
+    }
+}
@@ -1,74 +1,506 @@
-use dive_deco::{BuehlmannConfig, BuehlmannModel, DecoModel, Gas, Pressure, Supersaturation};
+use dive_deco::{
+    BuehlmannConfig, BuehlmannModel, Compartment, Deco, DecoModel, Gas, Minutes, Pressure,
+    Supersaturation,
+};
 
-use crate::common::{GradientFactorsSetting, GF};
+use crate::common::{
+    self, Average, Density, GradientFactorsSetting, MbarPressure, Temperature, Timestamp,
+    VerticalSpeed, GF,
+};
+use crate::consumption::{self, GasConsumption};
+use crate::error::AnalysisError;
+use crate::ox_tox::{Cns, Otu, OxTox};
+use crate::parser::Mix;
 use crate::parser::WaypointElem;
-use crate::stats::TimeBelowDepthData;
+use crate::parser::{DiverElem, PersonElem, PieceElem, SiteElem};
+use crate::stats::{self, AscentRateLimits, TimeBelowDepthData};
 use crate::{
     common::{Depth, Seconds},
     parser::DiveElem,
     stats::GasMixesData,
 };
 
-#[derive(Debug)]
+// NDL and TTS resolution, deco model simulations recalculated at most once per interval
+const RECALCULATION_INTERVAL: Seconds = 60;
+// bar, breathed gas below is hypoxic
+const HYPOXIC_PP_O2: Pressure = 0.16;
+// °C per meter, gradual temperature changes not reported as thermocline
+const THERMOCLINE_GRADIENT_MIN: Temperature = 0.5;
+
+#[derive(Clone, Debug)]
 pub struct DiveMeta {
     gradient_factors: GradientFactorsSetting,
+    // actual depth to deco model depth ratio, model assumes 0.1 bar per meter
+    depth_factor: f64,
     current_mix: Gas,
     last_depth: Depth,
+    // depth integrated over dive time (m * s), for time-weighted average depth
+    depth_time: f64,
+    ox_tox: OxTox,
+    ascent_rate_limits: AscentRateLimits,
+    // last segment ascent rate was above limit
+    fast_ascent: bool,
+    // last waypoint depth was shallower than ceiling
+    ceiling_violated: bool,
+    // gases used in dive, available for TTS deco calculation
+    deco_gases: Vec<Gas>,
+    // model state before first sample, for tissues replay
+    initial_model: Option<BuehlmannModel>,
+    // water temperature samples
+    temperature: Average,
+    // dive time and result of last NDL and TTS deco calculation
+    last_ndl: Option<(Seconds, Minutes)>,
+    last_tts: Option<(Seconds, Option<Minutes>)>,
+    // breathing circuit from UDDF dive mode
+    circuit: Circuit,
+    // bar
+    setpoint: Option<Pressure>,
+    // gas breathed from dive time, including loop gas changes, for tissues replay
+    breathing_gases: Vec<(Seconds, Gas)>,
+    // bar, ppO2 above is beyond gas MOD
+    pp_o2_max: Pressure,
+    // last waypoint gas was hypoxic or beyond MOD
+    hypoxic: bool,
+    above_mod: bool,
 }
 
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Circuit {
+    #[default]
+    Open,
+    Closed,
+    SemiClosed,
+}
+
+#[derive(Clone, Debug)]
 pub struct Dive {
+    // source file path
+    pub source: String,
+    // repetition group number in source file
+    pub repetition_group: usize,
+    // dive index in repetition group
+    pub group_index: usize,
+    // start date and time (ISO 8601)
+    pub date: Option<String>,
+    pub gradient_factors: GradientFactorsSetting,
+    pub surface_pressure: MbarPressure,
+    // kg/m3, deco model default (~1020) if unknown
+    pub water_density: Option<Density>,
+    // surface interval before dive if tissues carried over from previous dive in repetition group
+    pub surface_interval: Option<Seconds>,
     pub total_time: Seconds,
     pub depth_max: Depth,
+    // time-weighted average of sampled depths
+    pub depth_avg: Depth,
     pub time_in_deco: Seconds,
     pub gf_surf_max: GF,
     pub gf_99_max: GF,
     pub gf_end: GF,
     pub time_below: TimeBelowDepthData,
+    pub pp_o2_max: Pressure,
+    // CNS% including residual from previous dives in repetition group
+    pub cns_max: Cns,
+    pub otu: Otu,
+    // deco model state at each sample
+    pub timeline: Vec<TimelinePoint>,
+    // gas switches (dive time, switched to gas)
+    pub gas_switches: Vec<(Seconds, Gas)>,
+    // deco stops reported by dive computer (dive time, stop depth, kind)
+    pub deco_stops: Vec<(Seconds, Depth, String)>,
+    // lowest NDL outside of deco, none if dive started in deco
+    pub ndl_min: Option<Minutes>,
+    pub tts_max: Minutes,
+    pub ceiling_max: Depth,
+    // none without tank data
+    pub gas_consumption: Option<GasConsumption>,
+    pub ascent_rate_max: VerticalSpeed,
+    pub descent_rate_max: VerticalSpeed,
+    // number of ascents above rate limit and total time above limit
+    pub fast_ascents: usize,
+    pub fast_ascent_time: Seconds,
+    // periods spent shallower than deco ceiling
+    pub ceiling_violations: Vec<CeilingViolation>,
+    // water temperature from samples, average of sampled values
+    pub temperature_min: Option<Temperature>,
+    pub temperature_avg: Option<Temperature>,
+    pub thermocline: Option<Thermocline>,
+    // time on rebreather loop (closed or semi-closed circuit) and on open circuit
+    pub loop_time: Seconds,
+    pub open_circuit_time: Seconds,
+    // dive times of switches from loop to open circuit
+    pub bailouts: Vec<Seconds>,
+    // gas breathed outside ppO2 limits and counterdiffusion switches
+    pub gas_warnings: Vec<GasWarning>,
+    // invalid dive data replaced with configured values
+    pub warnings: Vec<AnalysisError>,
+    pub info: DiveInfo,
     meta: DiveMeta,
 }
 
+// logged dive details, not calculated from samples
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct DiveInfo {
+    pub dive_number: Option<u32>,
+    // logbook owner
+    pub diver: Option<Person>,
+    pub site: Option<Site>,
+    pub buddies: Vec<Person>,
+    pub air_temperature: Option<Temperature>,
+    // lowest water temperature
+    pub water_temperature: Option<Temperature>,
+    // meters
+    pub visibility: Option<f64>,
+    // dive computer summary
+    pub greatest_depth: Option<Depth>,
+    pub average_depth: Option<Depth>,
+    pub duration: Option<Seconds>,
+    // paragraphs joined with new lines
+    pub notes: Option<String>,
+    // 1-10
+    pub rating: Option<u8>,
+    // logbook owner equipment linked to dive or its tanks
+    pub equipment: Vec<Equipment>,
+}
+
+#[derive(Clone, Debug, PartialEq, PartialOrd)]
+pub struct Site {
+    pub id: String,
+    pub name: Option<String>,
+    pub location: Option<String>,
+    // degrees
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    // meters above sea level
+    pub altitude: Option<f64>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Person {
+    pub id: String,
+    pub name: Option<String>,
+    // organization and level, eg. "PADI Open Water"
+    pub certifications: Vec<String>,
+}
+
+#[derive(Clone, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Equipment {
+    pub id: String,
+    // UDDF element name, eg. "regulator" or "divecomputer"
+    pub kind: String,
+    pub name: Option<String>,
+    pub manufacturer: Option<String>,
+    pub model: Option<String>,
+    pub serial_number: Option<String>,
+    pub purchase_date: Option<String>,
+    pub service_interval_days: Option<u32>,
+    pub next_service_date: Option<String>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct TimelinePoint {
+    pub time: Seconds,
+    pub depth: Depth,
+    // ceiling in actual depth, 0 if no deco obligation
+    pub ceiling: Depth,
+    // none when in deco, capped at 99 min by deco model
+    pub ndl: Option<Minutes>,
+    // none if deco calculation failed for dive gases
+    pub tts: Option<Minutes>,
+    pub gf_99: GF,
+    pub pp_o2: Pressure,
+    // none if not sampled
+    pub temperature: Option<Temperature>,
+}
+
+// largest temperature change between sampled depths (whole meters)
+#[derive(Clone, Debug, PartialEq)]
+pub struct Thermocline {
+    pub depth_from: Depth,
+    pub depth_to: Depth,
+    // temperature at deeper depth minus temperature at shallower one
+    pub temperature_change: Temperature,
+}
+
+// tissues state replayed to dive time
+#[derive(Clone, Debug)]
+pub struct TissuesSnapshot {
+    pub time: Seconds,
+    pub depth: Depth,
+    // bar
+    pub ambient_pressure: Pressure,
+    pub compartments: Vec<Compartment>,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct CeilingViolation {
+    // dive time of last waypoint before violation
+    pub start: Seconds,
+    pub duration: Seconds,
+    // max depth difference above ceiling
+    pub depth_difference_max: Depth,
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct GasWarning {
+    pub kind: GasWarningKind,
+    // dive time of switch, or last waypoint before gas outside limits
+    pub start: Seconds,
+    // 0 for switches
+    pub duration: Seconds,
+    pub gas: Gas,
+    // gas switched from, for counterdiffusion
+    pub previous_gas: Option<Gas>,
+    // lowest ppO2 when hypoxic, highest beyond MOD, at switch for counterdiffusion
+    pub pp_o2: Pressure,
+    pub depth: Depth,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GasWarningKind {
+    // ppO2 below minimum (gas above its minimum operating depth)
+    Hypoxic,
+    // ppO2 above configured limit
+    AboveMod,
+    // switch raising helium while lowering nitrogen fraction
+    Counterdiffusion,
+}
+
+// tissues state after dive, carried over to the next dive in repetition group
+#[derive(Clone, Debug)]
+pub struct TissuesState {
+    pub model: BuehlmannModel,
+    pub ox_tox: OxTox,
+    pub dive_end: Option<Timestamp>,
+}
+
 pub struct DiveConfig {
     pub gradient_factors: GradientFactorsSetting,
+    pub surface_pressure: MbarPressure,
+    pub water_density: Option<Density>,
     pub treshold_depths: Vec<Depth>,
+    pub ascent_rate_limits: AscentRateLimits,
+    // bar
+    pub pp_o2_max: Pressure,
 }
 
 impl Dive {
     pub fn new(config: DiveConfig) -> Dive {
         let init_gas = Gas::new(0.21, 0.);
+        let depth_factor = match config.water_density {
+            Some(water_density) => common::pressure_per_meter(water_density) * 10.,
+            None => 1.,
+        };
         let dive_meta = DiveMeta {
             gradient_factors: config.gradient_factors,
+            depth_factor,
             current_mix: init_gas,
             last_depth: 0.,
+            depth_time: 0.,
+            ox_tox: OxTox::default(),
+            ascent_rate_limits: config.ascent_rate_limits,
+            fast_ascent: false,
+            ceiling_violated: false,
+            deco_gases: vec![],
+            last_ndl: None,
+            last_tts: None,
+            initial_model: None,
+            temperature: Average::default(),
+            circuit: Circuit::Open,
+            setpoint: None,
+            breathing_gases: vec![],
+            pp_o2_max: config.pp_o2_max,
+            hypoxic: false,
+            above_mod: false,
         };
 
         Dive {
+            source: String::new(),
+            repetition_group: 0,
+            group_index: 0,
+            date: None,
+            gradient_factors: config.gradient_factors,
+            surface_pressure: config.surface_pressure,
+            water_density: config.water_density,
+            surface_interval: None,
             total_time: 0,
             depth_max: 0.0,
+            depth_avg: 0.,
             time_in_deco: 0,
             gf_surf_max: 0.,
             gf_99_max: 0.,
             gf_end: 0.,
             time_below: Self::construct_treshold_depths(config.treshold_depths),
+            pp_o2_max: 0.,
+            cns_max: 0.,
+            otu: 0.,
+            timeline: vec![],
+            gas_switches: vec![],
+            deco_stops: vec![],
+            ndl_min: None,
+            tts_max: 0,
+            ceiling_max: 0.,
+            gas_consumption: None,
+            ascent_rate_max: 0.,
+            descent_rate_max: 0.,
+            fast_ascents: 0,
+            fast_ascent_time: 0,
+            ceiling_violations: vec![],
+            temperature_min: None,
+            temperature_avg: None,
+            thermocline: None,
+            loop_time: 0,
+            open_circuit_time: 0,
+            bailouts: vec![],
+            gas_warnings: vec![],
+            warnings: vec![],
+            info: DiveInfo::default(),
             meta: dive_meta,
         }
     }
 
-    pub fn calc_dive_stats(&mut self, dive_data: &DiveElem, gas_mixes: &GasMixesData) {
+    pub fn calc_dive_stats(
+        &mut self,
+        dive_data: &DiveElem,
+        gas_mixes: &GasMixesData,
+        previous_tissues: Option<TissuesState>,
+    ) -> Result<TissuesState, AnalysisError> {
+        self.date = dive_data.information_before_dive.date_time.clone();
         let (gf_lo, gf_hi) = self.meta.gradient_factors;
-        let mut model = BuehlmannModel::new(
-            BuehlmannConfig::new()
-                .with_gradient_factors(gf_lo, gf_hi)
-                .with_ceiling_type(dive_deco::CeilingType::Adaptive),
-        );
+        let model_config = BuehlmannConfig::new()
+            .with_gradient_factors(gf_lo, gf_hi)
+            .with_surface_pressure(self.surface_pressure)
+            .with_ceiling_type(dive_deco::CeilingType::Adaptive);
+        let mut model = match previous_tissues {
+            Some(previous_tissues) => {
+                self.carry_over_tissues(previous_tissues, dive_data, model_config)
+            }
+            None => BuehlmannModel::new(model_config),
+        };
+        self.meta.initial_model = Some(model.clone());
         // calc by data point
         let mut last_waypoint_time: Seconds = 0;
-        let dive_data_points = &dive_data.samples.waypoints;
+        let dive_data_points = match &dive_data.samples {
+            Some(samples) if !samples.waypoints.is_empty() => &samples.waypoints,
+            _ => return Err(AnalysisError::NoSamples),
+        };
+        self.meta.deco_gases = Self::dive_gases(dive_data_points, gas_mixes);
         for data_point in dive_data_points {
-            self.process_data_point(&mut model, &data_point, last_waypoint_time, &gas_mixes);
+            self.process_data_point(&mut model, data_point, last_waypoint_time, gas_mixes)?;
             // update last waypoint time
             last_waypoint_time = data_point.dive_time;
         }
+        if self.total_time > 0 {
+            self.depth_avg = self.meta.depth_time / self.total_time as f64;
+        }
+        self.temperature_avg = self.meta.temperature.value();
+        self.thermocline = Self::thermocline(&self.timeline);
+        self.gas_consumption = consumption::calc_gas_consumption(
+            dive_data_points,
+            dive_data.tank_data.as_deref().unwrap_or_default(),
+            |depth| self.ambient_pressure(depth),
+        );
+
+        let dive_end = self
+            .date
+            .as_deref()
+            .and_then(common::parse_timestamp)
+            .map(|dive_start| dive_start + self.total_time as Timestamp);
+        Ok(TissuesState {
+            model,
+            ox_tox: self.meta.ox_tox,
+            dive_end,
+        })
+    }
+
+    // previous dive tissues off-gassed at surface for the surface interval,
+    // fresh tissues if interval unknown
+    fn carry_over_tissues(
+        &mut self,
+        previous_tissues: TissuesState,
+        dive_data: &DiveElem,
+        model_config: BuehlmannConfig,
+    ) -> BuehlmannModel {
+        let TissuesState {
+            mut model,
+            mut ox_tox,
+            dive_end,
+        } = previous_tissues;
+        let surface_interval = match Self::surface_interval(dive_data, dive_end) {
+            Some(surface_interval) => surface_interval,
+            None => return BuehlmannModel::new(model_config),
+        };
+        // off-gassing breathing air at dive surface pressure (altitude), not previous dive one
+        if model.update_config(model_config).is_err() {
+            return BuehlmannModel::new(model_config);
+        }
+        model.record(0., surface_interval, &Gas::air());
+        ox_tox.surface_interval(surface_interval);
+        self.meta.ox_tox = ox_tox;
+        self.cns_max = ox_tox.cns;
+        self.surface_interval = Some(surface_interval);
+        model
+    }
+
+    // surface interval from previous dive end and dive start timestamps,
+    // UDDF surface interval as fallback
+    fn surface_interval(
+        dive_data: &DiveElem,
+        previous_dive_end: Option<Timestamp>,
+    ) -> Option<Seconds> {
+        let info = &dive_data.information_before_dive;
+        let dive_start = info.date_time.as_deref().and_then(common::parse_timestamp);
+        if let (Some(dive_start), Some(previous_dive_end)) = (dive_start, previous_dive_end) {
+            if dive_start >= previous_dive_end {
+                return Some((dive_start - previous_dive_end) as Seconds);
+            }
+        }
+        let passed_time = info.surface_interval_before_dive.as_ref()?.passed_time?;
+        match passed_time >= 0. {
+            true => Some(passed_time as Seconds),
+            false => None,
+        }
+    }
+
+    // per-sample data dropped once dive is analysed, aggregates and events kept,
+    // profile and tissues replay not available after
+    pub fn drop_samples(&mut self) {
+        self.timeline = vec![];
+        self.meta.breathing_gases = vec![];
+        self.meta.initial_model = None;
+    }
+
+    // tissues state at dive time replayed from timeline samples,
+    // with actual ceiling for replay speed, tissue loadings not affected
+    pub fn tissues_at(&self, time: Seconds) -> Option<TissuesSnapshot> {
+        let mut model = Self::actual_ceiling_model(self.meta.initial_model.as_ref()?);
+        let mut gas = Gas::new(0.21, 0.);
+        let mut gas_switches = self.meta.breathing_gases.iter().peekable();
+        let mut last_point: Option<&TimelinePoint> = None;
+        for point in self.timeline.iter().take_while(|point| point.time <= time) {
+            while let Some((_, switch_gas)) =
+                gas_switches.next_if(|(switch_time, _)| *switch_time <= point.time)
+            {
+                gas = *switch_gas;
+            }
+            let step_time = point.time - last_point.map_or(0, |last_point| last_point.time);
+            model.record(self.model_depth(point.depth), step_time, &gas);
+            last_point = Some(point);
+        }
+        let depth = last_point.map_or(0., |point| point.depth);
+        Some(TissuesSnapshot {
+            time: last_point.map_or(0, |point| point.time),
+            depth,
+            ambient_pressure: self.ambient_pressure(depth),
+            compartments: model.tissues(),
+        })
+    }
+
+    // date as "YYYY-MM-DD HH:MM"
+    pub fn date_readable(&self) -> String {
+        match &self.date {
+            Some(date) => date.replace('T', " ").chars().take(16).collect(),
+            None => "-".to_string(),
+        }
     }
 
     fn process_data_point(
@@ -77,44 +509,181 @@ impl Dive {
         data_point: &WaypointElem,
         last_waypoint_time: Seconds,
         gas_mixes: &GasMixesData,
-    ) {
+    ) -> Result<(), AnalysisError> {
         // time
-        let step_time: Seconds = data_point.dive_time - last_waypoint_time;
+        let step_time: Seconds = data_point
+            .dive_time
+            .checked_sub(last_waypoint_time)
+            .ok_or(AnalysisError::InvalidTime(data_point.dive_time))?;
         self.total_time += step_time;
 
+        // depth validation, deco model accepts only positive depths
+        if data_point.depth.is_nan() || data_point.depth < 0. {
+            return Err(AnalysisError::InvalidDepth(
+                data_point.dive_time,
+                data_point.depth,
+            ));
+        }
+
         // depth
         self.register_depth(&data_point.depth, &step_time);
+        self.register_vertical_speed(data_point.depth, step_time);
 
         // check for gas switch
-        let switchmix = &data_point.switchmix;
-        match switchmix {
-            Some(switchmix) => {
-                let gas_ref = &switchmix.gas_ref;
-                let gas = Self::gas_by_ref(gas_ref, gas_mixes);
-                match gas {
-                    Some(gas) => {
-                        self.meta.current_mix = gas;
-                    }
-                    None => {
-                        panic!("Gas not found");
-                    }
-                }
+        if let Some(switchmix) = &data_point.switchmix {
+            let previous_mix = self.meta.current_mix;
+            self.meta.current_mix = Self::gas_by_ref(&switchmix.gas_ref, gas_mixes)?;
+            self.register_counterdiffusion(previous_mix, data_point);
+            self.gas_switches
+                .push((data_point.dive_time, self.meta.current_mix));
+        }
+
+        // water temperature
+        let temperature = data_point.temperature.map(common::kelvin_to_celsius);
+        if let Some(temperature) = temperature {
+            self.register_temperature(temperature);
+        }
+
+        // dive mode, switch from loop to open circuit is a bailout
+        if let Some(dive_mode) = &data_point.dive_mode {
+            let circuit = Circuit::from_uddf(&dive_mode.kind);
+            if self.meta.circuit.is_loop() && !circuit.is_loop() {
+                self.bailouts.push(data_point.dive_time);
             }
-            None => (),
+            self.meta.circuit = circuit;
+        }
+        if let Some(set_po2) = &data_point.set_po2 {
+            self.meta.setpoint = Some(set_po2.value / 100_000.).filter(|setpoint| *setpoint > 0.);
+        }
+        match self.meta.circuit.is_loop() {
+            true => self.loop_time += step_time,
+            false => self.open_circuit_time += step_time,
+        }
+
+        // deco stops
+        for decostop in data_point.decostops.as_deref().unwrap_or_default() {
+            self.deco_stops.push((
+                data_point.dive_time,
+                decostop.deco_depth.unwrap_or(data_point.depth),
+                decostop.kind.clone(),
+            ));
         }
 
         // deco model step
-        let gas = &self.meta.current_mix;
-        model.step(data_point.depth, step_time, gas);
+        let gas = self.breathing_gas(data_point);
+        if self
+            .meta
+            .breathing_gases
+            .last()
+            .map(|(_, last_gas)| *last_gas)
+            != Some(gas)
+        {
+            self.meta.breathing_gases.push((data_point.dive_time, gas));
+        }
+        let gas = &gas;
+        let model_depth = self.model_depth(data_point.depth);
+        model.record(model_depth, step_time, gas);
+
+        // oxygen toxicity
+        let pp_o2 = gas.partial_pressures(model_depth, self.surface_pressure).o2;
+        self.register_ox_tox(pp_o2, step_time);
+        self.register_pp_o2_limits(*gas, pp_o2, data_point, step_time);
 
         // GFs
         let Supersaturation { gf_99, gf_surf } = model.supersaturation();
-        self.register_gfs((gf_99, gf_surf), &step_time, &data_point.depth);
+        self.register_gfs((gf_99, gf_surf), &data_point.depth);
 
         // deco time
-        if model.ceiling() > 0. {
+        let ceiling = model.ceiling();
+        if ceiling > 0. {
             self.time_in_deco += step_time;
         }
+
+        // ceiling violation, ceiling converted from deco model depth
+        let ceiling_depth = ceiling / self.meta.depth_factor;
+        self.register_ceiling(
+            ceiling_depth,
+            data_point.depth,
+            data_point.dive_time,
+            step_time,
+        );
+
+        // timeline, entering or leaving deco recalculates NDL and TTS
+        let (ndl, tts) = match ceiling > 0. {
+            true => {
+                self.meta.last_ndl = None;
+                (None, self.deco_tts(model, data_point))
+            }
+            // direct ascent without deco obligation, as in deco model runtime
+            false => {
+                self.meta.last_tts = None;
+                let ascent_time = (model_depth / model.config().deco_ascent_rate * 60.) as Seconds;
+                (
+                    Some(self.ndl(model, data_point)),
+                    Some(ascent_time.div_ceil(60)),
+                )
+            }
+        };
+        self.register_timeline_point(TimelinePoint {
+            time: data_point.dive_time,
+            depth: data_point.depth,
+            ceiling: ceiling_depth,
+            ndl,
+            tts,
+            gf_99,
+            pp_o2,
+            temperature,
+        });
+
+        Ok(())
+    }
+
+    // open circuit mix, on loop gas with measured ppO2 (sensors average) or setpoint,
+    // balanced with diluent inert gases, diluent if ppO2 unknown
+    fn breathing_gas(&self, data_point: &WaypointElem) -> Gas {
+        let mix = self.meta.current_mix;
+        if !self.meta.circuit.is_loop() {
+            return mix;
+        }
+        let sensors: Vec<Pressure> = data_point
+            .measured_po2
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .map(|sensor| sensor.value / 100_000.)
+            .filter(|pp_o2| pp_o2.is_finite() && *pp_o2 > 0.)
+            .collect();
+        let pp_o2 = match sensors.is_empty() {
+            true => match self.meta.setpoint {
+                Some(setpoint) => setpoint,
+                None => return mix,
+            },
+            false => sensors.iter().sum::<Pressure>() / sensors.len() as f64,
+        };
+        // loop ppO2 not lower than diluent ppO2 and not higher than ambient pressure
+        let diluent = mix.gas_pressures_compound(1.);
+        let o2 = (pp_o2 / self.ambient_pressure(data_point.depth)).clamp(diluent.o2, 1.);
+        let inert = diluent.n2 + diluent.he;
+        let he = match inert > 0. {
+            true => ((1. - o2) * diluent.he / inert).min(1. - o2),
+            false => 0.,
+        };
+        Gas::new(o2, he)
+    }
+
+    // lowest sampled water temperature, logged one if not sampled
+    pub fn water_temperature_min(&self) -> Option<Temperature> {
+        self.temperature_min.or(self.info.water_temperature)
+    }
+
+    // depth equivalent in deco model for water density
+    fn model_depth(&self, depth: Depth) -> Depth {
+        depth * self.meta.depth_factor
+    }
+
+    // ambient pressure (bar) at depth, as in deco model
+    fn ambient_pressure(&self, depth: Depth) -> Pressure {
+        self.surface_pressure as f64 / 1000. + self.model_depth(depth) / 10.
     }
 
     fn register_depth(&mut self, depth: &Depth, step_time: &Seconds) {
@@ -122,16 +691,271 @@ impl Dive {
         if depth > &self.depth_max {
             self.depth_max = *depth;
         }
+        // average, depth changing linearly between samples
+        self.meta.depth_time += (self.meta.last_depth + depth) / 2. * *step_time as f64;
         // treshold depths
-        for time_below_item in &mut self.time_below {
-            let (treshold_depth, mut current_time) = time_below_item;
+        for (treshold_depth, time_below) in &mut self.time_below {
             if depth >= treshold_depth {
-                time_below_item.1 += step_time;
+                *time_below += step_time;
+            }
+        }
+    }
+
+    // vertical speed from last waypoint, ascent rate limit for depth ascended from
+    fn register_vertical_speed(&mut self, depth: Depth, step_time: Seconds) {
+        if step_time == 0 {
+            return;
+        }
+        let last_depth = self.meta.last_depth;
+        let ascent_rate = (last_depth - depth) / step_time as f64 * 60.;
+        if ascent_rate > self.ascent_rate_max {
+            self.ascent_rate_max = ascent_rate;
+        }
+        if -ascent_rate > self.descent_rate_max {
+            self.descent_rate_max = -ascent_rate;
+        }
+        let fast_ascent = match stats::ascent_rate_limit(&self.meta.ascent_rate_limits, last_depth)
+        {
+            Some(limit) => ascent_rate > limit,
+            None => false,
+        };
+        if fast_ascent {
+            self.fast_ascent_time += step_time;
+            if !self.meta.fast_ascent {
+                self.fast_ascents += 1;
+            }
+        }
+        self.meta.fast_ascent = fast_ascent;
+    }
+
+    // switch to gas with higher helium and lower nitrogen fraction,
+    // at first waypoint initial mix set, not switched from
+    fn register_counterdiffusion(&mut self, previous_mix: Gas, data_point: &WaypointElem) {
+        if self.timeline.is_empty() {
+            return;
+        }
+        let previous = previous_mix.gas_pressures_compound(1.);
+        let next = self.meta.current_mix.gas_pressures_compound(1.);
+        if next.he > previous.he && next.n2 < previous.n2 {
+            self.gas_warnings.push(GasWarning {
+                kind: GasWarningKind::Counterdiffusion,
+                start: data_point.dive_time,
+                duration: 0,
+                gas: self.meta.current_mix,
+                previous_gas: Some(previous_mix),
+                pp_o2: self
+                    .meta
+                    .current_mix
+                    .partial_pressures(self.model_depth(data_point.depth), self.surface_pressure)
+                    .o2,
+                depth: data_point.depth,
+            });
+        }
+    }
+
+    // periods of breathing hypoxic gas or gas beyond MOD, as with ceiling violations
+    fn register_pp_o2_limits(
+        &mut self,
+        gas: Gas,
+        pp_o2: Pressure,
+        data_point: &WaypointElem,
+        step_time: Seconds,
+    ) {
+        // hypoxic mixes only, not air at altitude
+        let hypoxic = pp_o2 < HYPOXIC_PP_O2 && gas.gas_pressures_compound(1.).o2 < 0.21;
+        let above_mod = pp_o2 > self.meta.pp_o2_max;
+        for (kind, outside_limit, was_outside_limit) in [
+            (GasWarningKind::Hypoxic, hypoxic, self.meta.hypoxic),
+            (GasWarningKind::AboveMod, above_mod, self.meta.above_mod),
+        ] {
+            if !outside_limit {
+                continue;
+            }
+            let worse = |warning: &GasWarning| match kind {
+                GasWarningKind::Hypoxic => pp_o2 < warning.pp_o2,
+                _ => pp_o2 > warning.pp_o2,
+            };
+            let ongoing = self
+                .gas_warnings
+                .iter_mut()
+                .rev()
+                .find(|warning| warning.kind == kind)
+                .filter(|_| was_outside_limit);
+            match ongoing {
+                Some(warning) => {
+                    warning.duration += step_time;
+                    if worse(warning) {
+                        warning.pp_o2 = pp_o2;
+                        warning.depth = data_point.depth;
+                        warning.gas = gas;
+                    }
+                }
+                None => self.gas_warnings.push(GasWarning {
+                    kind,
+                    start: data_point.dive_time - step_time,
+                    duration: step_time,
+                    gas,
+                    previous_gas: None,
+                    pp_o2,
+                    depth: data_point.depth,
+                }),
+            }
+        }
+        self.meta.hypoxic = hypoxic;
+        self.meta.above_mod = above_mod;
+    }
+
+    fn register_ceiling(
+        &mut self,
+        ceiling: Depth,
+        depth: Depth,
+        dive_time: Seconds,
+        step_time: Seconds,
+    ) {
+        let violated = depth < ceiling;
+        if violated {
+            let depth_difference = ceiling - depth;
+            match (
+                self.meta.ceiling_violated,
+                self.ceiling_violations.last_mut(),
+            ) {
+                (true, Some(violation)) => {
+                    violation.duration += step_time;
+                    if depth_difference > violation.depth_difference_max {
+                        violation.depth_difference_max = depth_difference;
+                    }
+                }
+                _ => self.ceiling_violations.push(CeilingViolation {
+                    start: dive_time - step_time,
+                    duration: step_time,
+                    depth_difference_max: depth_difference,
+                }),
+            }
+        }
+        self.meta.ceiling_violated = violated;
+    }
+
+    // NDL simulated on model copy with actual ceiling, adaptive ceiling runs full deco
+    // simulation for every NDL minute, recalculated as TTS
+    fn ndl(&mut self, model: &BuehlmannModel, data_point: &WaypointElem) -> Minutes {
+        if let Some((last_time, last_ndl)) = self.meta.last_ndl {
+            if !Self::recalculation_due(last_time, data_point) {
+                return last_ndl;
             }
         }
+        let ndl = Self::actual_ceiling_model(model).ndl();
+        self.meta.last_ndl = Some((data_point.dive_time, ndl));
+        ndl
     }
 
-    fn register_gfs(&mut self, gfs: (Pressure, Pressure), time: &Seconds, depth: &Depth) {
+    // TTS in deco from deco model runtime, simulation of all stops is costly,
+    // on loop as open circuit bailout TTS on dive gases
+    fn deco_tts(&mut self, model: &BuehlmannModel, data_point: &WaypointElem) -> Option<Minutes> {
+        if let Some((last_time, last_tts)) = self.meta.last_tts {
+            if !Self::recalculation_due(last_time, data_point) {
+                return last_tts;
+            }
+        }
+        let mut sim_model = Self::actual_ceiling_model(model);
+        // bailout starting on diluent, deco runtime requires current gas in available gases
+        if self.meta.circuit.is_loop() {
+            sim_model.record(
+                self.model_depth(data_point.depth),
+                0,
+                &self.meta.current_mix,
+            );
+        }
+        // simulated deco skips TTS in 5 minutes runtime, not reported
+        let tts = Deco::new_sim()
+            .calc(sim_model, self.meta.deco_gases.clone())
+            .ok()
+            .map(|deco| deco.tts);
+        self.meta.last_tts = Some((data_point.dive_time, tts));
+        tts
+    }
+
+    // last deco calculation outdated after recalculation interval or gas or dive mode switch
+    fn recalculation_due(last_time: Seconds, data_point: &WaypointElem) -> bool {
+        data_point.dive_time >= last_time + RECALCULATION_INTERVAL
+            || data_point.switchmix.is_some()
+            || data_point.dive_mode.is_some()
+    }
+
+    fn actual_ceiling_model(model: &BuehlmannModel) -> BuehlmannModel {
+        let mut sim_model = model.clone();
+        let sim_config = model
+            .config()
+            .with_ceiling_type(dive_deco::CeilingType::Actual);
+        if sim_model.update_config(sim_config).is_err() {
+            return model.clone();
+        }
+        sim_model
+    }
+
+    fn register_timeline_point(&mut self, point: TimelinePoint) {
+        if let Some(ndl) = point.ndl {
+            self.ndl_min = Some(self.ndl_min.map_or(ndl, |ndl_min| ndl_min.min(ndl)));
+        }
+        if let Some(tts) = point.tts {
+            self.tts_max = self.tts_max.max(tts);
+        }
+        if point.ceiling > self.ceiling_max {
+            self.ceiling_max = point.ceiling;
+        }
+        self.timeline.push(point);
+    }
+
+    fn register_temperature(&mut self, temperature: Temperature) {
+        self.meta.temperature.add(temperature);
+        self.temperature_min = Some(self.temperature_min.map_or(temperature, |temperature_min| {
+            temperature_min.min(temperature)
+        }));
+    }
+
+    // samples averaged by depth meter, largest change per meter between consecutive sampled depths,
+    // none if not steeper than minimal gradient
+    fn thermocline(timeline: &[TimelinePoint]) -> Option<Thermocline> {
+        let mut depths: Vec<(i64, Average)> = vec![];
+        for point in timeline {
+            if let Some(temperature) = point.temperature {
+                let mut average = Average::default();
+                average.add(temperature);
+                common::add_sorted(
+                    &mut depths,
+                    point.depth.floor() as i64,
+                    average,
+                    i64::cmp,
+                    Average::merge,
+                );
+            }
+        }
+        depths
+            .windows(2)
+            .filter_map(|pair| {
+                let ((depth_from, from), (depth_to, to)) = (&pair[0], &pair[1]);
+                Some(Thermocline {
+                    depth_from: *depth_from as Depth,
+                    depth_to: *depth_to as Depth,
+                    temperature_change: to.value()? - from.value()?,
+                })
+            })
+            .max_by(|a, b| a.gradient().abs().total_cmp(&b.gradient().abs()))
+            .filter(|thermocline| thermocline.gradient().abs() >= THERMOCLINE_GRADIENT_MIN)
+    }
+
+    fn register_ox_tox(&mut self, pp_o2: Pressure, step_time: Seconds) {
+        if pp_o2 > self.pp_o2_max {
+            self.pp_o2_max = pp_o2;
+        }
+        let otu_before = self.meta.ox_tox.otu;
+        self.meta.ox_tox.record(pp_o2, step_time);
+        self.otu += self.meta.ox_tox.otu - otu_before;
+        if self.meta.ox_tox.cns > self.cns_max {
+            self.cns_max = self.meta.ox_tox.cns;
+        }
+    }
+
+    fn register_gfs(&mut self, gfs: (Pressure, Pressure), depth: &Depth) {
         let (gf_99, gf_surf) = gfs;
         // GF surf
         if gf_surf > self.gf_surf_max {
@@ -149,33 +973,917 @@ impl Dive {
         self.meta.last_depth = *depth;
     }
 
-    fn update_time_in_deco(&mut self, time: Seconds) {
-        self.time_in_deco += time;
-    }
-
-    // @todo
-    fn gas_by_ref(gas_ref: &str, gas_mixes: &GasMixesData) -> Option<Gas> {
-        let mut gas: Option<Gas> = None;
-        match gas_mixes {
-            Some(gas_mixes) => {
-                for mix_definition in gas_mixes {
-                    if mix_definition.id == gas_ref {
-                        gas = Some(Gas::new(mix_definition.o2, mix_definition.he.unwrap_or(0.)));
-                    }
+    // gases switched to in dive, starting with air if dive doesn't start with gas switch
+    fn dive_gases(waypoints: &[WaypointElem], gas_mixes: &GasMixesData) -> Vec<Gas> {
+        let mut gases = vec![];
+        let starts_with_switch = waypoints
+            .first()
+            .is_some_and(|waypoint| waypoint.switchmix.is_some());
+        if !starts_with_switch {
+            gases.push(Gas::air());
+        }
+        for switchmix in waypoints
+            .iter()
+            .filter_map(|waypoint| waypoint.switchmix.as_ref())
+        {
+            if let Ok(gas) = Self::gas_by_ref(&switchmix.gas_ref, gas_mixes) {
+                if !gases.contains(&gas) {
+                    gases.push(gas);
                 }
             }
-            None => {
-                panic!("No gas mixes, can't process switch");
-            }
         }
-        gas
+        gases
+    }
+
+    fn gas_by_ref(gas_ref: &str, gas_mixes: &GasMixesData) -> Result<Gas, AnalysisError> {
+        let gas_mixes = gas_mixes
+            .as_ref()
+            .ok_or_else(|| AnalysisError::NoGasMixes(gas_ref.to_string()))?;
+        let mix_definition = gas_mixes
+            .iter()
+            .find(|mix| mix.id == gas_ref)
+            .ok_or_else(|| AnalysisError::GasNotFound(gas_ref.to_string()))?;
+        Self::gas_from_mix(mix_definition)
+    }
+
+    // validated before passing to deco model, which panics on invalid fractions
+    fn gas_from_mix(mix_definition: &Mix) -> Result<Gas, AnalysisError> {
+        let o2 = mix_definition.o2;
+        let he = mix_definition.he.unwrap_or(0.);
+        let fraction_range = 0. ..=1.;
+        if !fraction_range.contains(&o2) || !fraction_range.contains(&he) || o2 + he > 1. {
+            return Err(AnalysisError::InvalidGasMix(mix_definition.id.clone()));
+        }
+        Ok(Gas::new(o2, he))
     }
 
     fn construct_treshold_depths(treshold_config: Vec<Depth>) -> TimeBelowDepthData {
         let mut time_below = vec![];
-        for depth in treshold_config {
+        for depth in stats::normalize_treshold_depths(treshold_config) {
             time_below.push((depth, 0));
         }
         time_below
     }
 }
+
+impl DiveInfo {
+    // dive details with site and buddies resolved from dive links, diver from logbook owner
+    pub fn new(dive_data: &DiveElem, sites: &[SiteElem], diver: Option<&DiverElem>) -> Self {
+        let info_before = &dive_data.information_before_dive;
+        let links: Vec<&str> = info_before
+            .links
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .map(|link| link.link_ref.as_str())
+            .collect();
+        let site = sites
+            .iter()
+            .find(|site| links.contains(&site.id.as_str()))
+            .map(Site::from);
+        let buddies = diver
+            .and_then(|diver| diver.buddies.as_deref())
+            .unwrap_or_default()
+            .iter()
+            .filter(|buddy| links.contains(&buddy.id.as_str()))
+            .map(Person::from)
+            .collect();
+
+        // equipment used and tanks
+        let tank_links = dive_data
+            .tank_data
+            .as_deref()
+            .unwrap_or_default()
+            .iter()
+            .flat_map(|tank| tank.links.as_deref().unwrap_or_default());
+        let equipment_links: Vec<&str> = info_before
+            .equipment_used
+            .as_ref()
+            .and_then(|equipment_used| equipment_used.links.as_deref())
+            .unwrap_or_default()
+            .iter()
+            .chain(tank_links)
+            .map(|link| link.link_ref.as_str())
+            .collect();
+        let equipment = diver
+            .and_then(|diver| diver.owner.as_ref())
+            .and_then(|owner| owner.equipment.as_ref())
+            .and_then(|equipment| equipment.pieces.as_deref())
+            .unwrap_or_default()
+            .iter()
+            .filter_map(|piece| piece.kind_and_piece())
+            .filter(|(_, piece)| equipment_links.contains(&piece.id.as_str()))
+            .map(|(kind, piece)| Equipment::new(kind, piece))
+            .collect();
+
+        let mut info = Self {
+            dive_number: info_before.dive_number,
+            diver: diver
+                .and_then(|diver| diver.owner.as_ref())
+                .map(Person::from),
+            site,
+            buddies,
+            air_temperature: info_before.air_temperature.map(common::kelvin_to_celsius),
+            equipment,
+            ..Self::default()
+        };
+        if let Some(info_after) = &dive_data.information_after_dive {
+            info.water_temperature = info_after.lowest_temperature.map(common::kelvin_to_celsius);
+            info.visibility = info_after.visibility;
+            info.greatest_depth = info_after.greatest_depth;
+            info.average_depth = info_after.average_depth;
+            info.duration = info_after
+                .dive_duration
+                .filter(|duration| *duration >= 0.)
+                .map(|duration| duration as Seconds);
+            info.notes = info_after
+                .notes
+                .as_ref()
+                .and_then(|notes| notes.para.as_ref())
+                .map(|paragraphs| paragraphs.join("\n"))
+                .filter(|notes| !notes.trim().is_empty());
+            info.rating = info_after
+                .ratings
+                .as_deref()
+                .unwrap_or_default()
+                .iter()
+                .find_map(|rating| rating.rating_value);
+        }
+        info
+    }
+
+    // buddy with name containing given text (case insensitive) or with given id
+    pub fn has_buddy(&self, buddy: &str) -> bool {
+        let buddy_lowercase = buddy.trim().to_lowercase();
+        self.buddies.iter().any(|person| {
+            person.id == buddy.trim()
+                || person
+                    .name_readable()
+                    .to_lowercase()
+                    .contains(&buddy_lowercase)
+        })
+    }
+}
+
+impl Person {
+    // name from personal data, id if none
+    pub fn name_readable(&self) -> String {
+        self.name.clone().unwrap_or(self.id.clone())
+    }
+
+    // eg. "Ann Owner (PADI Open Water, SSI Nitrox)"
+    pub fn name_with_certifications(&self) -> String {
+        match self.certifications.is_empty() {
+            true => self.name_readable(),
+            false => format!(
+                "{} ({})",
+                self.name_readable(),
+                self.certifications.join(", ")
+            ),
+        }
+    }
+
+    // same person from other file, certifications from both kept sorted,
+    // smaller id kept so that result doesn't depend on merge order
+    pub fn merge(&mut self, other: Person) {
+        self.certifications.extend(other.certifications);
+        self.certifications.sort();
+        self.certifications.dedup();
+        if other.id < self.id {
+            self.id = other.id;
+            self.name = other.name;
+        }
+    }
+}
+
+impl From<&PersonElem> for Person {
+    fn from(person: &PersonElem) -> Self {
+        let name = person.personal.as_ref().and_then(|personal| {
+            let full_name = [&personal.first_name, &personal.last_name]
+                .into_iter()
+                .flatten()
+                .map(|name| name.trim())
+                .collect::<Vec<&str>>()
+                .join(" ");
+            match full_name.is_empty() {
+                true => personal.nick_name.clone(),
+                false => Some(full_name),
+            }
+        });
+        let certifications = person
+            .education
+            .as_ref()
+            .and_then(|education| education.certifications.as_deref())
+            .unwrap_or_default()
+            .iter()
+            .map(|certification| {
+                [&certification.organization, &certification.level]
+                    .into_iter()
+                    .flatten()
+                    .map(|part| part.trim())
+                    .collect::<Vec<&str>>()
+                    .join(" ")
+            })
+            .filter(|certification| !certification.is_empty())
+            .collect();
+        Self {
+            id: person.id.clone(),
+            name,
+            certifications,
+        }
+    }
+}
+
+impl Equipment {
+    pub fn new(kind: &str, piece: &PieceElem) -> Self {
+        Self {
+            id: piece.id.clone(),
+            kind: kind.to_string(),
+            name: piece.name.clone(),
+            manufacturer: piece
+                .manufacturer
+                .as_ref()
+                .and_then(|manufacturer| manufacturer.name.clone()),
+            model: piece.model.clone(),
+            serial_number: piece.serial_number.clone(),
+            purchase_date: piece
+                .purchase
+                .as_ref()
+                .and_then(|purchase| purchase.date_time.clone()),
+            service_interval_days: piece.service_interval,
+            next_service_date: piece
+                .next_service_date
+                .as_ref()
+                .and_then(|date| date.date_time.clone()),
+        }
+    }
+
+    // name, manufacturer with model or id
+    pub fn name_readable(&self) -> String {
+        if let Some(name) = &self.name {
+            return name.clone();
+        }
+        let model: Vec<&str> = [&self.manufacturer, &self.model]
+            .into_iter()
+            .flatten()
+            .map(|part| part.as_str())
+            .collect();
+        match model.is_empty() {
+            true => self.id.clone(),
+            false => model.join(" "),
+        }
+    }
+
+    // name with serial number, identifies piece across files
+    pub fn label(&self) -> String {
+        match &self.serial_number {
+            Some(serial_number) => format!("{} ({serial_number})", self.name_readable()),
+            None => self.name_readable(),
+        }
+    }
+
+    pub fn kind_readable(&self) -> &str {
+        match self.kind.as_str() {
+            "buoyancycontroldevice" => "BCD",
+            "divecomputer" => "dive computer",
+            "videocamera" => "video camera",
+            "variouspieces" => "other",
+            kind => kind,
+        }
+    }
+
+    // from next service date and service interval, purchase date if unknown
+    pub fn last_service_timestamp(&self) -> Option<Timestamp> {
+        let next_service = self
+            .next_service_date
+            .as_deref()
+            .and_then(common::parse_timestamp);
+        match (next_service, self.service_interval_days) {
+            (Some(next_service), Some(days)) => Some(next_service - days as Timestamp * 86400),
+            _ => self
+                .purchase_date
+                .as_deref()
+                .and_then(common::parse_timestamp),
+        }
+    }
+}
+
+impl Circuit {
+    // from UDDF dive mode kind, apnoe as open circuit
+    pub fn from_uddf(kind: &str) -> Self {
+        match kind.trim() {
+            "closedcircuit" => Self::Closed,
+            "semiclosedcircuit" => Self::SemiClosed,
+            _ => Self::Open,
+        }
+    }
+
+    // closed or semi-closed circuit rebreather
+    pub fn is_loop(&self) -> bool {
+        matches!(self, Self::Closed | Self::SemiClosed)
+    }
+}
+
+impl Thermocline {
+    // temperature change per meter
+    pub fn gradient(&self) -> f64 {
+        self.temperature_change / (self.depth_to - self.depth_from)
+    }
+}
+
+impl Site {
+    pub fn name_readable(&self) -> String {
+        self.name.clone().unwrap_or(self.id.clone())
+    }
+
+    // name with location, identifies site across files
+    pub fn full_name(&self) -> String {
+        match &self.location {
+            Some(location) => format!("{}, {location}", self.name_readable()),
+            None => self.name_readable(),
+        }
+    }
+
+    pub fn coordinates_readable(&self) -> Option<String> {
+        let (latitude, longitude) = (self.latitude?, self.longitude?);
+        let altitude = match self.altitude {
+            Some(altitude) if altitude != 0. => format!(", {altitude}m a.s.l."),
+            _ => String::new(),
+        };
+        Some(format!("{latitude:.5}, {longitude:.5}{altitude}"))
+    }
+}
+
+impl From<&SiteElem> for Site {
+    fn from(site: &SiteElem) -> Self {
+        let geography = site.geography.as_ref();
+        Self {
+            id: site.id.clone(),
+            name: site.name.clone(),
+            location: geography.and_then(|geography| geography.location.clone()),
+            latitude: geography.and_then(|geography| geography.latitude),
+            longitude: geography.and_then(|geography| geography.longitude),
+            altitude: geography.and_then(|geography| geography.altitude),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::parser::SurfaceIntervalElem;
+
+    fn dive_config() -> DiveConfig {
+        DiveConfig {
+            gradient_factors: (30, 70),
+            surface_pressure: common::DEFAULT_SURFACE_PRESSURE,
+            water_density: None,
+            treshold_depths: vec![],
+            ascent_rate_limits: vec![(0., 9.)],
+            pp_o2_max: 1.6,
+        }
+    }
+
+    fn dive() -> Dive {
+        Dive::new(dive_config())
+    }
+
+    // dive on air with waypoints every interval between (dive time, depth) profile points
+    fn profile_dive(profile: &[(Seconds, Depth)], interval: Seconds) -> DiveElem {
+        let mut waypoints = String::new();
+        for pair in profile.windows(2) {
+            let ((start, start_depth), (end, end_depth)) = (pair[0], pair[1]);
+            for time in (start..end).step_by(interval as usize) {
+                let depth = start_depth
+                    + (end_depth - start_depth) * (time - start) as f64 / (end - start) as f64;
+                waypoints += &format!(
+                    "<waypoint><divetime>{time}</divetime><depth>{depth}</depth></waypoint>"
+                );
+            }
+        }
+        let (end, end_depth) = profile[profile.len() - 1];
+        waypoints +=
+            &format!("<waypoint><divetime>{end}</divetime><depth>{end_depth}</depth></waypoint>");
+        quick_xml::de::from_str(&format!(
+            "<dive><informationbeforedive/><samples>{waypoints}</samples></dive>"
+        ))
+        .unwrap()
+    }
+
+    #[test]
+    fn square_profile_ndl_tts_and_ceiling() {
+        let mut dive = dive();
+        let dive_data = profile_dive(&[(0, 0.), (120, 40.), (1320, 40.), (1560, 0.)], 10);
+        dive.calc_dive_stats(&dive_data, &None, None).unwrap();
+        let point = |time| {
+            dive.timeline
+                .iter()
+                .find(|point| point.time == time)
+                .unwrap()
+        };
+
+        // no deco on descent, direct ascent TTS
+        assert_eq!((point(0).ndl, point(0).tts), (Some(99), Some(0)));
+        assert_eq!((point(120).ndl, point(120).tts), (Some(3), Some(4)));
+        assert_eq!(point(390).ceiling, 0.);
+        assert_eq!(point(390).ndl, Some(0));
+        // deco from 7th minute, TTS recalculated on entering deco
+        assert!(point(420).ceiling > 0.);
+        assert_eq!((point(420).ndl, point(420).tts), (None, Some(6)));
+        assert_eq!(point(1320).tts, Some(53));
+        assert!((point(1320).ceiling - 15.82).abs() < 0.01);
+
+        // NDL and deco TTS held between recalculations once per minute
+        for pair in dive.timeline.windows(2) {
+            if pair[1].time % RECALCULATION_INTERVAL != 0 {
+                assert_eq!(pair[1].ndl, pair[0].ndl);
+                if pair[0].ceiling > 0. {
+                    assert_eq!(pair[1].tts, pair[0].tts);
+                }
+            }
+        }
+        // bottom NDL decreasing, TTS increasing
+        let bottom = dive
+            .timeline
+            .iter()
+            .filter(|point| (120..=1320).contains(&point.time));
+        for (last, point) in bottom.clone().zip(bottom.skip(1)) {
+            assert!(point.ndl.unwrap_or(0) <= last.ndl.unwrap_or(0));
+            assert!(point.tts >= last.tts);
+        }
+
+        // summary from timeline
+        assert_eq!(dive.ndl_min, Some(0));
+        assert_eq!(dive.tts_max, 53);
+        let ceiling_max = dive
+            .timeline
+            .iter()
+            .map(|point| point.ceiling)
+            .fold(0., f64::max);
+        assert_eq!(dive.ceiling_max, ceiling_max);
+        assert!((dive.ceiling_max - 15.82).abs() < 0.01);
+    }
+
+    // (dive time, ceiling, depth) waypoints
+    fn register_ceilings(dive: &mut Dive, waypoints: &[(Seconds, Depth, Depth)]) {
+        let mut last_time = 0;
+        for (time, ceiling, depth) in waypoints {
+            dive.register_ceiling(*ceiling, *depth, *time, time - last_time);
+            last_time = *time;
+        }
+    }
+
+    #[test]
+    fn registers_ascent_through_ceiling() {
+        let mut dive = dive();
+        register_ceilings(
+            &mut dive,
+            &[
+                (60, 6., 9.),
+                (120, 6., 5.),
+                (180, 5., 2.),
+                (240, 4., 3.),
+                (300, 3., 6.),
+                (360, 3., 3.),
+            ],
+        );
+        assert_eq!(
+            dive.ceiling_violations,
+            vec![CeilingViolation {
+                start: 60,
+                duration: 180,
+                depth_difference_max: 3.,
+            }]
+        );
+    }
+
+    #[test]
+    fn no_violation_below_ceiling() {
+        let mut dive = dive();
+        register_ceilings(
+            &mut dive,
+            &[(60, 6., 9.), (120, 6., 6.), (180, 3., 3.5), (240, 0., 0.)],
+        );
+        assert!(dive.ceiling_violations.is_empty());
+    }
+
+    #[test]
+    fn violation_ends_with_dive() {
+        let mut dive = dive();
+        register_ceilings(
+            &mut dive,
+            &[
+                (60, 3., 1.),
+                (120, 3., 6.),
+                (180, 6., 9.),
+                (240, 4., 3.),
+                (300, 3., 0.),
+            ],
+        );
+        assert_eq!(
+            dive.ceiling_violations,
+            vec![
+                CeilingViolation {
+                    start: 0,
+                    duration: 60,
+                    depth_difference_max: 2.,
+                },
+                CeilingViolation {
+                    start: 180,
+                    duration: 120,
+                    depth_difference_max: 3.,
+                },
+            ]
+        );
+    }
+
+    fn waypoint(dive_time: Seconds, depth: Depth) -> WaypointElem {
+        quick_xml::de::from_str(&format!(
+            "<waypoint><divetime>{dive_time}</divetime><depth>{depth}</depth></waypoint>"
+        ))
+        .unwrap()
+    }
+
+    fn mix(id: &str, o2: f64, he: Option<f64>) -> Mix {
+        Mix {
+            id: id.to_string(),
+            name: id.to_string(),
+            o2,
+            n2: None,
+            he,
+        }
+    }
+
+    fn gas_warning_kinds(dive: &Dive) -> Vec<GasWarningKind> {
+        dive.gas_warnings
+            .iter()
+            .map(|warning| warning.kind)
+            .collect()
+    }
+
+    #[test]
+    fn hypoxic_below_minimum_pp_o2_on_hypoxic_mix() {
+        let tx10 = Gas::new(0.1, 0.7);
+        let register = |gas, pp_o2| {
+            let mut dive = dive();
+            dive.register_pp_o2_limits(gas, pp_o2, &waypoint(60, 0.), 60);
+            gas_warning_kinds(&dive)
+        };
+        assert_eq!(register(tx10, 0.159), vec![GasWarningKind::Hypoxic]);
+        assert!(register(tx10, HYPOXIC_PP_O2).is_empty());
+        // air at altitude
+        assert!(register(Gas::new(0.21, 0.), 0.159).is_empty());
+        assert_eq!(
+            register(Gas::new(0.2, 0.), 0.159),
+            vec![GasWarningKind::Hypoxic]
+        );
+    }
+
+    #[test]
+    fn above_mod_beyond_pp_o2_max() {
+        let ean50 = Gas::new(0.5, 0.);
+        let register = |pp_o2| {
+            let mut dive = dive();
+            dive.register_pp_o2_limits(ean50, pp_o2, &waypoint(60, 22.), 60);
+            gas_warning_kinds(&dive)
+        };
+        assert!(register(1.6).is_empty());
+        assert_eq!(register(1.601), vec![GasWarningKind::AboveMod]);
+    }
+
+    #[test]
+    fn counterdiffusion_on_switch_to_more_helium_and_less_nitrogen() {
+        let gas_mixes = Some(vec![
+            mix("air", 0.21, None),
+            mix("ean32", 0.32, None),
+            mix("tx21/35", 0.21, Some(0.35)),
+            mix("tx18/45", 0.18, Some(0.45)),
+            mix("tx21/45", 0.21, Some(0.45)),
+        ]);
+        let switches = |from: &str, to: &str| {
+            let mut dive = dive();
+            let dive_data = quick_xml::de::from_str(&format!(
+                "<dive><informationbeforedive/><samples>\
+                <waypoint><divetime>0</divetime><depth>0</depth><switchmix ref=\"{from}\"/></waypoint>\
+                <waypoint><divetime>60</divetime><depth>20</depth></waypoint>\
+                <waypoint><divetime>120</divetime><depth>20</depth><switchmix ref=\"{to}\"/></waypoint>\
+                <waypoint><divetime>180</divetime><depth>0</depth></waypoint>\
+                </samples></dive>"
+            ))
+            .unwrap();
+            dive.calc_dive_stats(&dive_data, &gas_mixes, None).unwrap();
+            gas_warning_kinds(&dive)
+        };
+        // nitrogen for helium
+        assert_eq!(
+            switches("air", "tx21/35"),
+            vec![GasWarningKind::Counterdiffusion]
+        );
+        assert_eq!(
+            switches("tx21/35", "tx18/45"),
+            vec![GasWarningKind::Counterdiffusion]
+        );
+        // helium for nitrogen, helium for oxygen, oxygen for nitrogen
+        assert!(switches("tx21/35", "air").is_empty());
+        assert!(switches("tx18/45", "tx21/35").is_empty());
+        assert!(switches("tx21/45", "tx18/45").is_empty());
+        assert!(switches("air", "ean32").is_empty());
+        // initial mix set at first waypoint
+        let mut dive = dive();
+        let dive_data = quick_xml::de::from_str(
+            "<dive><informationbeforedive/><samples>\
+            <waypoint><divetime>0</divetime><depth>0</depth><switchmix ref=\"tx21/35\"/></waypoint>\
+            <waypoint><divetime>60</divetime><depth>0</depth></waypoint>\
+            </samples></dive>",
+        )
+        .unwrap();
+        dive.calc_dive_stats(&dive_data, &gas_mixes, None).unwrap();
+        assert!(dive.gas_warnings.is_empty());
+    }
+
+    // ascent rate limits 9 m/min from 6 m, 3 m/min shallower, (dive time, depth) waypoints
+    fn register_vertical_speeds(waypoints: &[(Seconds, Depth)]) -> Dive {
+        let mut dive = dive();
+        dive.meta.ascent_rate_limits = vec![(0., 3.), (6., 9.)];
+        let (mut last_time, first_depth) = waypoints[0];
+        dive.meta.last_depth = first_depth;
+        for (time, depth) in &waypoints[1..] {
+            dive.register_vertical_speed(*depth, time - last_time);
+            dive.meta.last_depth = *depth;
+            last_time = *time;
+        }
+        dive
+    }
+
+    #[test]
+    fn fast_ascent_by_limit_for_depth_ascended_from() {
+        let fast_ascents = |waypoints: &[(Seconds, Depth)]| {
+            let dive = register_vertical_speeds(waypoints);
+            (dive.fast_ascents, dive.fast_ascent_time)
+        };
+        // deep band
+        assert_eq!(fast_ascents(&[(0, 20.), (60, 11.1)]), (0, 0));
+        assert_eq!(fast_ascents(&[(0, 20.), (60, 10.9)]), (1, 60));
+        // shallow band
+        assert_eq!(fast_ascents(&[(0, 5.), (60, 2.1)]), (0, 0));
+        assert_eq!(fast_ascents(&[(0, 5.), (60, 1.9)]), (1, 60));
+        // ascending from deep band into shallow one
+        assert_eq!(fast_ascents(&[(0, 7.), (60, 3.)]), (0, 0));
+        assert_eq!(fast_ascents(&[(0, 6.), (60, 2.)]), (0, 0));
+    }
+
+    #[test]
+    fn continuous_fast_ascent_counted_once() {
+        let dive = register_vertical_speeds(&[
+            (0, 30.),
+            (30, 24.),
+            (60, 18.),
+            (90, 12.),
+            (150, 9.),
+            (180, 3.),
+        ]);
+        assert_eq!((dive.fast_ascents, dive.fast_ascent_time), (2, 120));
+        assert_eq!(dive.ascent_rate_max, 12.);
+    }
+
+    // closed circuit dive on diluent, inspired gases passed to deco model as
+    // (dive time, o2, he) after each waypoint
+    fn loop_inspired_gases(diluent: &str, waypoints: &str) -> (Dive, Vec<(Seconds, f64, f64)>) {
+        let gas_mixes = Some(vec![
+            mix("air", 0.21, None),
+            mix("tx21/35", 0.21, Some(0.35)),
+            mix("ean50", 0.5, None),
+        ]);
+        let dive_data = quick_xml::de::from_str(&format!(
+            "<dive><informationbeforedive/><samples>\
+            <waypoint><divetime>0</divetime><depth>0</depth><switchmix ref=\"{diluent}\"/>\
+            <divemode kind=\"closedcircuit\"/></waypoint>{waypoints}</samples></dive>"
+        ))
+        .unwrap();
+        let mut dive = dive();
+        dive.calc_dive_stats(&dive_data, &gas_mixes, None).unwrap();
+        let gases = dive
+            .meta
+            .breathing_gases
+            .iter()
+            .map(|(time, gas)| {
+                let fractions = gas.gas_pressures_compound(1.);
+                (*time, fractions.o2, fractions.he)
+            })
+            .collect();
+        (dive, gases)
+    }
+
+    fn assert_inspired(gases: &[(Seconds, f64, f64)], expected: &[(Seconds, f64, f64)]) {
+        assert_eq!(gases.len(), expected.len(), "{gases:?}");
+        for ((time, o2, he), (expected_time, expected_o2, expected_he)) in
+            gases.iter().zip(expected)
+        {
+            assert_eq!(time, expected_time);
+            assert!((o2 - expected_o2).abs() < 1e-9, "{gases:?}");
+            assert!((he - expected_he).abs() < 1e-9, "{gases:?}");
+        }
+    }
+
+    // bar, ambient pressure at 20 m
+    const AMBIENT_20M: Pressure = 1.013 + 2.;
+
+    #[test]
+    fn loop_gas_from_setpoint_in_pascal() {
+        let (_, gases) = loop_inspired_gases(
+            "air",
+            "<waypoint><divetime>60</divetime><depth>20</depth><setpo2>130000</setpo2></waypoint>\
+            <waypoint><divetime>120</divetime><depth>20</depth></waypoint>",
+        );
+        // diluent until setpoint known
+        assert_inspired(&gases, &[(0, 0.21, 0.), (60, 1.3 / AMBIENT_20M, 0.)]);
+    }
+
+    #[test]
+    fn loop_gas_from_measured_pp_o2_average() {
+        let (_, gases) = loop_inspired_gases(
+            "tx21/35",
+            "<waypoint><divetime>60</divetime><depth>20</depth><setpo2>100000</setpo2>\
+            <measuredpo2>120000</measuredpo2><measuredpo2>130000</measuredpo2>\
+            <measuredpo2>140000</measuredpo2></waypoint>",
+        );
+        // inert gases balanced as in diluent
+        let o2 = 1.3 / AMBIENT_20M;
+        assert_inspired(
+            &gases,
+            &[(0, 0.21, 0.35), (60, o2, (1. - o2) * 0.35 / 0.79)],
+        );
+    }
+
+    #[test]
+    fn loop_gas_clamped_to_diluent_and_pure_oxygen() {
+        let (_, gases) = loop_inspired_gases(
+            "air",
+            "<waypoint><divetime>60</divetime><depth>20</depth><measuredpo2>30000</measuredpo2></waypoint>\
+            <waypoint><divetime>120</divetime><depth>20</depth><measuredpo2>70000</measuredpo2></waypoint>\
+            <waypoint><divetime>300</divetime><depth>3</depth><setpo2>140000</setpo2></waypoint>",
+        );
+        // low ppO2 clamped to diluent, unchanged gas not passed again
+        assert_inspired(
+            &gases,
+            &[(0, 0.21, 0.), (120, 0.7 / AMBIENT_20M, 0.), (300, 1., 0.)],
+        );
+    }
+
+    #[test]
+    fn loop_gas_switched_to_bailout_on_dive_mode_change() {
+        let (dive, gases) = loop_inspired_gases(
+            "air",
+            "<waypoint><divetime>60</divetime><depth>20</depth><setpo2>130000</setpo2></waypoint>\
+            <waypoint><divetime>120</divetime><depth>20</depth><divemode kind=\"opencircuit\"/>\
+            </waypoint>\
+            <waypoint><divetime>300</divetime><depth>20</depth><switchmix ref=\"ean50\"/></waypoint>",
+        );
+        assert_inspired(
+            &gases,
+            &[
+                (0, 0.21, 0.),
+                (60, 1.3 / AMBIENT_20M, 0.),
+                (120, 0.21, 0.),
+                (300, 0.5, 0.),
+            ],
+        );
+        assert_eq!(dive.bailouts, vec![120]);
+        assert_eq!((dive.loop_time, dive.open_circuit_time), (60, 240));
+    }
+
+    // descent by meter to 20 m, temperature in Kelvin by depth
+    fn descent_with_temperatures(kelvin: impl Fn(Depth) -> f64) -> Dive {
+        let mut waypoints = String::new();
+        for depth in 0..=20 {
+            waypoints += &format!(
+                "<waypoint><divetime>{}</divetime><depth>{depth}</depth>\
+                <temperature>{}</temperature></waypoint>",
+                depth * 10,
+                kelvin(depth as Depth)
+            );
+        }
+        let dive_data = quick_xml::de::from_str(&format!(
+            "<dive><informationbeforedive/><samples>{waypoints}</samples></dive>"
+        ))
+        .unwrap();
+        let mut dive = dive();
+        dive.calc_dive_stats(&dive_data, &None, None).unwrap();
+        dive
+    }
+
+    #[test]
+    fn thermocline_at_temperature_step() {
+        let dive = descent_with_temperatures(|depth| match depth > 12. {
+            true => 285.15,
+            false => 293.15,
+        });
+        assert_eq!(
+            dive.thermocline,
+            Some(Thermocline {
+                depth_from: 12.,
+                depth_to: 13.,
+                temperature_change: -8.,
+            })
+        );
+        // Kelvin converted to °C
+        assert_eq!(dive.temperature_min, Some(12.));
+        assert_eq!(dive.timeline[0].temperature, Some(20.));
+    }
+
+    #[test]
+    fn no_thermocline_in_gradual_gradient() {
+        let dive = descent_with_temperatures(|depth| 293.15 - depth * 0.2);
+        assert_eq!(dive.thermocline, None);
+        assert_eq!(dive.temperature_min, Some(16.));
+    }
+
+    // 30 m for 25 minutes on air
+    fn square_dive() -> DiveElem {
+        profile_dive(&[(0, 0.), (60, 30.), (1500, 30.), (1740, 0.)], 60)
+    }
+
+    // square dive repeated after surface interval, at surface pressure
+    fn repetitive_dive(surface_pressure: MbarPressure, surface_interval: Option<f64>) -> Dive {
+        let mut first_dive = dive();
+        let tissues = first_dive
+            .calc_dive_stats(&square_dive(), &None, None)
+            .unwrap();
+        let mut dive_data = square_dive();
+        dive_data
+            .information_before_dive
+            .surface_interval_before_dive =
+            surface_interval.map(|passed_time| SurfaceIntervalElem {
+                passed_time: Some(passed_time),
+            });
+        let mut dive = dive();
+        dive.surface_pressure = surface_pressure;
+        dive.calc_dive_stats(&dive_data, &None, Some(tissues))
+            .unwrap();
+        dive
+    }
+
+    fn initial_n2(dive: &Dive) -> Vec<Pressure> {
+        let model = dive.meta.initial_model.as_ref().unwrap();
+        model.tissues().iter().map(|tissue| tissue.n2_ip).collect()
+    }
+
+    #[test]
+    fn residual_nitrogen_carried_over_surface_interval() {
+        let mut first_dive = dive();
+        first_dive
+            .calc_dive_stats(&square_dive(), &None, None)
+            .unwrap();
+        let dive = repetitive_dive(common::DEFAULT_SURFACE_PRESSURE, Some(3600.));
+        assert_eq!(dive.surface_interval, Some(3600));
+        assert!(dive.gf_surf_max > first_dive.gf_surf_max);
+        assert!(dive.ceiling_max > first_dive.ceiling_max);
+        assert_eq!((first_dive.tts_max, dive.tts_max), (28, 50));
+        // off-gassed at surface, tissues not as loaded as at first dive end
+        let first_dive_end = first_dive.timeline.last().unwrap().gf_99;
+        assert!(dive.timeline[0].gf_99 < first_dive_end);
+    }
+
+    #[test]
+    fn fresh_tissues_without_surface_interval() {
+        let mut first_dive = dive();
+        first_dive
+            .calc_dive_stats(&square_dive(), &None, None)
+            .unwrap();
+        let dive = repetitive_dive(common::DEFAULT_SURFACE_PRESSURE, None);
+        assert_eq!(dive.surface_interval, None);
+        assert_eq!(initial_n2(&dive), initial_n2(&first_dive));
+        assert_eq!(dive.gf_surf_max, first_dive.gf_surf_max);
+        assert_eq!(dive.tts_max, first_dive.tts_max);
+    }
+
+    #[test]
+    fn surface_interval_off_gassing_at_dive_surface_pressure() {
+        let sea_level = repetitive_dive(common::DEFAULT_SURFACE_PRESSURE, Some(3600.));
+        let altitude = repetitive_dive(800, Some(3600.));
+        // less nitrogen inspired at lower surface pressure
+        for (sea_level_n2, altitude_n2) in initial_n2(&sea_level).iter().zip(initial_n2(&altitude))
+        {
+            assert!(altitude_n2 < *sea_level_n2);
+        }
+    }
+
+    #[test]
+    fn fresh_water_depth_greater_than_salt_water_one_for_same_pressure() {
+        let depth_factor = |water_density| {
+            Dive::new(DiveConfig {
+                water_density,
+                ..dive_config()
+            })
+            .meta
+            .depth_factor
+        };
+        let fresh = depth_factor(Some(common::FRESH_WATER_DENSITY));
+        let salt = depth_factor(Some(common::SALT_WATER_DENSITY));
+        // deco model 10 m, 1 bar
+        assert!(10. / fresh > 10. / salt);
+        assert!((10. / salt - 9.95).abs() < 0.01);
+        assert!((10. / fresh - 10.2).abs() < 0.01);
+        assert_eq!(depth_factor(None), 1.);
+    }
+}
@@ -0,0 +1,83 @@
+use crate::common::{Depth, Seconds};
+use std::{error::Error, fmt};
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum AnalysisError {
+    // file couldn't be read
+    Io(String),
+    // malformed XML or document not matching UDDF structure
+    Parse(String),
+    // gas switch without any gas definitions in file
+    NoGasMixes(String),
+    // gas switch to undefined mix
+    GasNotFound(String),
+    // gas mix with invalid fractions
+    InvalidGasMix(String),
+    // dive without waypoints
+    NoSamples,
+    // negative or not a number depth
+    InvalidDepth(Seconds, Depth),
+    // waypoint dive time earlier than previous one
+    InvalidTime(Seconds),
+    // surface pressure (Pascal) not a number or out of deco model range
+    InvalidSurfacePressure(String),
+    // repetition group and dive index no longer in file
+    DiveNotFound(usize, usize),
+}
+
+impl fmt::Display for AnalysisError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::Io(details) => write!(f, "Unable to read file ({details})"),
+            Self::Parse(details) => write!(f, "Invalid UDDF ({details})"),
+            Self::NoGasMixes(gas_ref) => {
+                write!(f, "Switch to gas [{gas_ref}] but no gas mixes defined")
+            }
+            Self::GasNotFound(gas_ref) => write!(f, "Gas [{gas_ref}] not found"),
+            Self::InvalidGasMix(gas_ref) => write!(f, "Gas [{gas_ref}] has invalid fractions"),
+            Self::NoSamples => write!(f, "No samples"),
+            Self::InvalidDepth(time, depth) => {
+                write!(f, "Invalid depth [{depth}] at {time}s")
+            }
+            Self::InvalidTime(time) => {
+                write!(f, "Dive time [{time}s] earlier than previous sample")
+            }
+            Self::InvalidSurfacePressure(pressure) => {
+                write!(f, "Surface pressure [{pressure}Pa] not in 500-1500mbar range")
+            }
+            Self::DiveNotFound(repetition_group, group_index) => write!(
+                f,
+                "Dive {} of group {} not found",
+                group_index + 1,
+                repetition_group + 1
+            ),
+        }
+    }
+}
+
+impl Error for AnalysisError {}
+
+// file or dive excluded from stats
+#[derive(Clone, Debug)]
+pub struct Skipped {
+    pub source: String,
+    // repetition group number and dive index in group, none if whole file skipped
+    pub dive: Option<(usize, usize)>,
+    pub error: AnalysisError,
+}
+
+impl fmt::Display for Skipped {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.dive {
+            Some((repetition_group, group_index)) => write!(
+                f,
+                "{} (group {} dive {}): {}",
+                self.source,
+                repetition_group + 1,
+                group_index + 1,
+                self.error
+            ),
+            None => write!(f, "{}: {}", self.source, self.error),
+        }
+    }
+}
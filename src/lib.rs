@@ -1,38 +1,302 @@
-#![allow(warnings)]
-
 mod common;
+mod consumption;
+mod error;
+mod ox_tox;
 mod parser;
 mod dive;
 mod stats;
 mod app;
 
 use std::error::Error;
+use std::fs;
+use std::io::{self, Write};
 
-use stats::Stats;
 use app::App;
 
+pub use dive::{Dive, TimelinePoint};
+pub use stats::{DiveSortKey, Stats, StatsConfig, StatsData};
+
+const USAGE: &str = "Usage: dive-reporter [COMMAND] [PATH]
+
+Commands:
+  gui [path]                 open the UI, optionally analysing a file or directory on start (default)
+  stats <path>               print logbook stats to the console
+  dives <path>               list analysed dives, in logbook order unless sorted with --sort
+  export <path> [-o file]    export stats as CSV (stdout if no output file given)
+  help                       print this message
+
+Options:
+  --sort <key>               dives list sorting: file, number, date, duration, depth, deco, gf99, gfsurf
+  --desc                     sort dives list in descending order
+  --timeline                 export timeline of all dives (ceiling, NDL, TTS, GF99, ppO2 per sample)
+  --config <file>            read settings from file, options passed in command line take precedence
+  --gf <low/high>            gradient factors for dives without deco model settings (default 30/70)
+  --ignore-dive-gf           use configured gradient factors even if dive data has its own
+  --depths <d1,d2,..>        depths in meters for time below depth stats (default 10,20,30,40)
+  --surface-pressure <mbar>  surface pressure for dives without one (default 1013)
+  --altitude <m>             surface pressure from altitude for dives without one
+  --water <salinity>         fresh, salt or density in kg/m3 for dives without density or site water
+  --ascent-rate <limits>     ascent rate limit in m/min, optionally from depth (eg. 0:9,30:18, default 9)
+  --buddy <name>             stats only from dives with buddy whose name contains given text, or with given id
+  --service-dives <n>        warn about equipment used in n or more dives since last service
+  --service-days <d>         service interval in days for equipment without one in dive data
+  --temperature-unit <unit>  temperature unit, c (default) or f
+  --ppo2-max <bar>           ppO2 limit for gas MOD warnings (default 1.6)
+
+Options can be given before or after the command.
+
+Config file contains one `setting = value` per line, settings named as options without dashes,
+eg. `gf = 40/85`, `depths = 5,18,30` or `ignore-dive-gf = true`. Lines starting with # are ignored.
+
+Exit status is 0 on success, 1 on error and 2 if some files or dives were skipped because of invalid data.";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Gui,
+    Stats,
+    Dives,
+    Export,
+    Help,
+}
 
 pub struct Config {
-    pub path: String,
+    pub command: Command,
+    pub path: Option<String>,
+    pub output: Option<String>,
+    pub stats_config: StatsConfig,
+    pub sort_key: DiveSortKey,
+    pub sort_descending: bool,
+    // export dives timeline instead of stats
+    pub export_timeline: bool,
 }
 
 impl Config {
-    pub fn build(mut args: impl Iterator<Item = String>) -> Result<Config, &'static str> {
-        args.next();
-        let path = match args.next() {
-            Some(arg) => arg,
-            // None => return Err("Path missing"),
-            None => "".to_owned()
-        };
+    pub fn build(args: impl Iterator<Item = String>) -> Result<Config, &'static str> {
+        let args: Vec<String> = args.skip(1).collect();
+        let mut stats_config = StatsConfig::default();
+
+        // config file settings first, so that command line options can override them
+        if let Some(i) = args.iter().position(|arg| arg == "--config") {
+            let config_path = args.get(i + 1).ok_or("Config file path missing")?;
+            Self::apply_config_file(&mut stats_config, config_path)?;
+        }
+
+        // options accepted before and after the command, first argument that is not an option
+        // is the command, next one the path
+        let mut args = args.into_iter();
+        let mut command: Option<Command> = None;
+        let mut path: Option<String> = None;
+        let mut output: Option<String> = None;
+        let mut sort_key = DiveSortKey::Source;
+        let mut sort_descending = false;
+        let mut export_timeline = false;
+        while let Some(arg) = args.next() {
+            match arg.as_str() {
+                "-h" | "--help" => {
+                    command = Some(Command::Help);
+                }
+                "-o" | "--output" => {
+                    output = Some(args.next().ok_or("Output file path missing")?);
+                }
+                "--config" => {
+                    args.next();
+                }
+                "--sort" => {
+                    let key = args.next().ok_or("Sort key missing")?;
+                    sort_key = stats::parse_dive_sort_key(&key).ok_or("Unknown sort key")?;
+                }
+                "--desc" => {
+                    sort_descending = true;
+                }
+                "--timeline" => {
+                    export_timeline = true;
+                }
+                "--ignore-dive-gf" => {
+                    Self::apply_setting(&mut stats_config, "ignore-dive-gf", "true")?;
+                }
+                _ if arg.starts_with("--") => {
+                    let value = args.next().ok_or("Option value missing")?;
+                    Self::apply_setting(&mut stats_config, &arg[2..], &value)?;
+                }
+                _ if arg.starts_with('-') => return Err("Unknown option"),
+                _ if command.is_none() => command = Some(Self::parse_command(&arg)?),
+                _ if path.is_none() => path = Some(arg),
+                _ => return Err("Unexpected argument"),
+            }
+        }
+
+        let command = command.unwrap_or(Command::Gui);
+        // UI analyses selected dive again for its profile, samples kept only for export
+        stats_config.keep_samples = export_timeline;
+        let path_required = matches!(command, Command::Stats | Command::Dives | Command::Export);
+        if path_required && path.is_none() {
+            return Err("Path missing");
+        }
+
         Ok(Config {
+            command,
             path,
+            output,
+            stats_config,
+            sort_key,
+            sort_descending,
+            export_timeline,
         })
     }
+
+    fn parse_command(arg: &str) -> Result<Command, &'static str> {
+        match arg {
+            "gui" => Ok(Command::Gui),
+            "stats" => Ok(Command::Stats),
+            "dives" => Ok(Command::Dives),
+            "export" => Ok(Command::Export),
+            "help" => Ok(Command::Help),
+            _ => Err("Unknown command, run with `help` for usage"),
+        }
+    }
+
+    fn apply_config_file(stats_config: &mut StatsConfig, path: &str) -> Result<(), &'static str> {
+        let content = fs::read_to_string(path).map_err(|_| "Unable to read config file")?;
+        for line in content.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let (setting, value) = line
+                .split_once('=')
+                .ok_or("Invalid config file line, expected `setting = value`")?;
+            let setting = setting.trim().replace('_', "-");
+            let value = value.trim().trim_matches('"');
+            Self::apply_setting(stats_config, &setting, value)?;
+        }
+        Ok(())
+    }
+
+    fn apply_setting(
+        stats_config: &mut StatsConfig,
+        setting: &str,
+        value: &str,
+    ) -> Result<(), &'static str> {
+        match setting {
+            "gf" => {
+                stats_config.gradient_factors = stats::parse_gradient_factors(value).ok_or(
+                    "Invalid gradient factors, expected low/high in 1-100 range (eg. 30/70)",
+                )?;
+            }
+            "ignore-dive-gf" => {
+                let ignore: bool = value.parse().map_err(|_| "Expected true or false")?;
+                stats_config.use_dive_gradient_factors = !ignore;
+            }
+            "surface-pressure" => {
+                stats_config.surface_pressure = stats::parse_surface_pressure(value)
+                    .ok_or("Invalid surface pressure, expected mbar in 500-1500 range")?;
+            }
+            "altitude" => {
+                let altitude: f64 = value.parse().map_err(|_| "Invalid altitude")?;
+                let surface_pressure = common::surface_pressure_at_altitude(altitude);
+                if !(500..=1500).contains(&surface_pressure) {
+                    return Err("Altitude out of deco model surface pressure range");
+                }
+                stats_config.surface_pressure = surface_pressure;
+            }
+            "water" => {
+                stats_config.water_density = Some(
+                    stats::parse_water_density(value)
+                        .ok_or("Invalid water, expected fresh, salt or density in 900-1100 kg/m3 range")?,
+                );
+            }
+            "ascent-rate" => {
+                stats_config.ascent_rate_limits = stats::parse_ascent_rate_limits(value).ok_or(
+                    "Invalid ascent rate, expected m/min or depth:rate list (eg. 9 or 0:9,30:18)",
+                )?;
+            }
+            "buddy" => {
+                let buddy = value.trim();
+                if buddy.is_empty() {
+                    return Err("Buddy name missing");
+                }
+                stats_config.buddy = Some(buddy.to_string());
+            }
+            "service-dives" => {
+                let service_dives: usize = value
+                    .parse()
+                    .map_err(|_| "Invalid service dives, expected number of dives")?;
+                stats_config.service_dives = (service_dives > 0).then_some(service_dives);
+            }
+            "service-days" => {
+                let service_days: u32 = value
+                    .parse()
+                    .map_err(|_| "Invalid service days, expected number of days")?;
+                stats_config.service_days = (service_days > 0).then_some(service_days);
+            }
+            "temperature-unit" => {
+                stats_config.temperature_unit = stats::parse_temperature_unit(value)
+                    .ok_or("Invalid temperature unit, expected c or f")?;
+            }
+            "ppo2-max" => {
+                stats_config.pp_o2_max = stats::parse_pp_o2_max(value)
+                    .ok_or("Invalid ppO2 limit, expected bar in 1.0-2.0 range")?;
+            }
+            "depths" => {
+                stats_config.treshold_depths = stats::parse_treshold_depths(value)
+                    .ok_or("Invalid depths, expected comma separated list (eg. 10,20,30)")?;
+            }
+            _ => return Err("Unknown option"),
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum Outcome {
+    Complete,
+    // some files or dives skipped because of invalid data
+    Incomplete,
+}
+
+pub fn run(config: Config) -> Result<Outcome, Box<dyn Error>> {
+    let stats = match config.command {
+        Command::Gui => {
+            App::new(config.path, config.stats_config).init()?;
+            return Ok(Outcome::Complete);
+        }
+        Command::Help => {
+            println!("{USAGE}");
+            return Ok(Outcome::Complete);
+        }
+        Command::Stats => {
+            let stats = analyse(&config)?;
+            stats.print_to_console();
+            stats
+        }
+        Command::Dives => {
+            let stats = analyse(&config)?;
+            stats.print_dives_to_console(config.sort_key, config.sort_descending);
+            stats
+        }
+        Command::Export => {
+            let stats = analyse(&config)?;
+            let csv = match config.export_timeline {
+                true => stats.timeline_to_csv(),
+                false => stats.to_csv(),
+            };
+            match &config.output {
+                Some(output) => fs::write(output, csv)?,
+                None => io::stdout().write_all(csv.as_bytes())?,
+            }
+            stats
+        }
+    };
+
+    stats.print_warnings_to_console();
+    stats.print_skipped_to_console();
+    let skipped_no = stats.stats_data.lock().unwrap().skipped.len();
+    match skipped_no {
+        0 => Ok(Outcome::Complete),
+        _ => Ok(Outcome::Incomplete),
+    }
 }
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    App::default().init();
-    // let stats = Stats::new().from_path(&config.path)?;
-    // stats.print();
-    Ok(())
+fn analyse(config: &Config) -> Result<Stats, Box<dyn Error>> {
+    let path = config.path.clone().unwrap_or_default();
+    Stats::with_config(config.stats_config.clone()).from_path(&path)
 }
@@ -1,5 +1,5 @@
 use std::{env, process, time};
-use dive_reporter::Config;
+use dive_reporter::{Config, Outcome};
 use colored::*;
 
 
@@ -13,11 +13,15 @@ fn main() {
         process::exit(1);
     });
 
-    if let Err(e) = dive_reporter::run(config) {
+    let outcome = dive_reporter::run(config).unwrap_or_else(|e| {
         eprintln!("Application error: {}", e);
         process::exit(1);
-    }
+    });
 
     let elapsed = format!("Elapsed: {:.2?}", timer.elapsed());
-    println!("{}", elapsed.dimmed());
+    eprintln!("{}", elapsed.dimmed());
+
+    if outcome == Outcome::Incomplete {
+        process::exit(2);
+    }
 }
@@ -0,0 +1,125 @@
+use dive_deco::Pressure;
+
+use crate::common::Seconds;
+
+pub type Cns = f64;
+pub type Otu = f64;
+
+// NOAA single exposure limits (ppO2, minutes), 0.5-0.6 segment extrapolated
+const CNS_LIMITS: [(Pressure, f64); 12] = [
+    (0.5, 900.),
+    (0.6, 720.),
+    (0.7, 570.),
+    (0.8, 450.),
+    (0.9, 360.),
+    (1.0, 300.),
+    (1.1, 240.),
+    (1.2, 210.),
+    (1.3, 180.),
+    (1.4, 150.),
+    (1.5, 120.),
+    (1.6, 45.),
+];
+const CNS_MIN_LIMIT_MINUTES: f64 = 1.;
+const CNS_ELIMINATION_HALF_TIME: Seconds = 90 * 60;
+const OTU_EXPONENT: f64 = 0.83;
+
+// oxygen exposure, CNS% and pulmonary toxicity units
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct OxTox {
+    pub cns: Cns,
+    pub otu: Otu,
+}
+
+impl OxTox {
+    pub fn record(&mut self, pp_o2: Pressure, time: Seconds) {
+        if pp_o2 <= 0.5 || time == 0 {
+            return;
+        }
+        let minutes = time as f64 / 60.;
+        self.cns += minutes / Self::cns_limit(pp_o2) * 100.;
+        self.otu += minutes * ((pp_o2 - 0.5) / 0.5).powf(OTU_EXPONENT);
+    }
+
+    // CNS elimination at surface, OTU recovery not modeled within a day
+    pub fn surface_interval(&mut self, time: Seconds) {
+        let half_times = time as f64 / CNS_ELIMINATION_HALF_TIME as f64;
+        self.cns /= 2_f64.powf(half_times);
+    }
+
+    // exposure time limit in minutes interpolated from NOAA table,
+    // extrapolated with the last segment above 1.6
+    fn cns_limit(pp_o2: Pressure) -> f64 {
+        let segment = CNS_LIMITS
+            .windows(2)
+            .find(|segment| pp_o2 <= segment[1].0)
+            .unwrap_or(&CNS_LIMITS[CNS_LIMITS.len() - 2..]);
+        let (pp_o2_start, limit_start) = segment[0];
+        let (pp_o2_end, limit_end) = segment[1];
+        let slope = (limit_end - limit_start) / (pp_o2_end - pp_o2_start);
+        let limit = limit_start + (pp_o2 - pp_o2_start) * slope;
+        limit.max(CNS_MIN_LIMIT_MINUTES)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_close(value: f64, expected: f64) {
+        assert!(
+            (value - expected).abs() < 1e-9,
+            "{value} not equal to {expected}"
+        );
+    }
+
+    #[test]
+    fn interpolates_cns_limits() {
+        assert_close(OxTox::cns_limit(0.6), 720.);
+        assert_close(OxTox::cns_limit(1.45), 135.);
+        assert_close(OxTox::cns_limit(1.4), 150.);
+        assert_close(OxTox::cns_limit(1.6), 45.);
+        // extrapolated with the 1.5-1.6 segment, limited to a minute
+        assert_close(OxTox::cns_limit(1.65), 7.5);
+        assert_close(OxTox::cns_limit(2.), CNS_MIN_LIMIT_MINUTES);
+    }
+
+    #[test]
+    fn records_cns_and_otu() {
+        let mut ox_tox = OxTox::default();
+        ox_tox.record(1.45, 135 * 60);
+        assert_close(ox_tox.cns, 100.);
+        let mut ox_tox = OxTox::default();
+        ox_tox.record(1.6, 9 * 60);
+        assert_close(ox_tox.cns, 20.);
+        // 1 OTU per minute at 1 bar
+        let mut ox_tox = OxTox::default();
+        ox_tox.record(1., 10 * 60);
+        assert_close(ox_tox.otu, 10.);
+        let mut ox_tox = OxTox::default();
+        ox_tox.record(1.5, 60);
+        assert_close(ox_tox.otu, 2_f64.powf(0.83));
+    }
+
+    #[test]
+    fn ignores_exposure_below_half_bar() {
+        let mut ox_tox = OxTox::default();
+        ox_tox.record(0.5, 3600);
+        ox_tox.record(0.21, 3600);
+        ox_tox.record(1.4, 0);
+        assert_eq!(ox_tox, OxTox::default());
+    }
+
+    #[test]
+    fn eliminates_cns_at_surface() {
+        let mut ox_tox = OxTox { cns: 80., otu: 50. };
+        ox_tox.surface_interval(90 * 60);
+        assert_close(ox_tox.cns, 40.);
+        ox_tox.surface_interval(180 * 60);
+        assert_close(ox_tox.cns, 10.);
+        ox_tox.surface_interval(0);
+        assert_close(ox_tox.cns, 10.);
+        // OTU kept within a day
+        assert_close(ox_tox.otu, 50.);
+    }
+}
@@ -1,16 +1,206 @@
-use std::io::{self, Read};
-use std::error::Error;
-use std::fs;
+use std::io::BufReader;
+use std::fs::File;
 use serde::Deserialize;
+use serde::de::DeserializeOwned;
+use quick_xml::Reader;
+use quick_xml::Writer;
+use quick_xml::events::{BytesStart, Event};
 use crate::common::{Depth, Seconds};
-use crate::stats;
+use crate::error::AnalysisError;
 
-#[derive(Deserialize)]
-pub struct UDDFDoc {
-    #[serde(rename = "profiledata")]
-    pub profile_data: ProfileDataElem,
-    #[serde(rename = "gasdefinitions")]
+// document level definitions referenced by dives
+pub struct UDDFHeader {
     pub gas_definitions: GasDefinition,
+    pub deco_model: Option<DecoModelElem>,
+    pub diver: Option<DiverElem>,
+    pub dive_site: Option<DiveSiteElem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiverElem {
+    pub owner: Option<PersonElem>,
+    #[serde(rename = "buddy")]
+    pub buddies: Option<Vec<PersonElem>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PersonElem {
+    #[serde(rename = "@id")]
+    pub id: String,
+    pub personal: Option<PersonalElem>,
+    pub education: Option<EducationElem>,
+    pub equipment: Option<EquipmentElem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PersonalElem {
+    #[serde(rename = "firstname")]
+    pub first_name: Option<String>,
+    #[serde(rename = "lastname")]
+    pub last_name: Option<String>,
+    #[serde(rename = "nickname")]
+    pub nick_name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EducationElem {
+    #[serde(rename = "certification")]
+    pub certifications: Option<Vec<CertificationElem>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CertificationElem {
+    pub organization: Option<String>,
+    pub level: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EquipmentElem {
+    #[serde(rename = "$value")]
+    pub pieces: Option<Vec<EquipmentPieceElem>>,
+}
+
+// equipment piece by kind, other elements (eg. equipment configurations) ignored
+#[derive(Debug, Deserialize)]
+pub enum EquipmentPieceElem {
+    #[serde(rename = "boots")]
+    Boots(PieceElem),
+    #[serde(rename = "buoyancycontroldevice")]
+    BuoyancyControlDevice(PieceElem),
+    #[serde(rename = "camera")]
+    Camera(PieceElem),
+    #[serde(rename = "compass")]
+    Compass(PieceElem),
+    #[serde(rename = "divecomputer")]
+    DiveComputer(PieceElem),
+    #[serde(rename = "fins")]
+    Fins(PieceElem),
+    #[serde(rename = "gloves")]
+    Gloves(PieceElem),
+    #[serde(rename = "knife")]
+    Knife(PieceElem),
+    #[serde(rename = "lead")]
+    Lead(PieceElem),
+    #[serde(rename = "light")]
+    Light(PieceElem),
+    #[serde(rename = "mask")]
+    Mask(PieceElem),
+    #[serde(rename = "rebreather")]
+    Rebreather(PieceElem),
+    #[serde(rename = "regulator")]
+    Regulator(PieceElem),
+    #[serde(rename = "scooter")]
+    Scooter(PieceElem),
+    #[serde(rename = "suit")]
+    Suit(PieceElem),
+    #[serde(rename = "tank")]
+    Tank(PieceElem),
+    #[serde(rename = "variouspieces")]
+    VariousPieces(PieceElem),
+    #[serde(rename = "videocamera")]
+    VideoCamera(PieceElem),
+    #[serde(rename = "watch")]
+    Watch(PieceElem),
+    #[serde(other)]
+    Other,
+}
+
+impl EquipmentPieceElem {
+    // UDDF element name and piece data
+    pub fn kind_and_piece(&self) -> Option<(&'static str, &PieceElem)> {
+        match self {
+            Self::Boots(piece) => Some(("boots", piece)),
+            Self::BuoyancyControlDevice(piece) => Some(("buoyancycontroldevice", piece)),
+            Self::Camera(piece) => Some(("camera", piece)),
+            Self::Compass(piece) => Some(("compass", piece)),
+            Self::DiveComputer(piece) => Some(("divecomputer", piece)),
+            Self::Fins(piece) => Some(("fins", piece)),
+            Self::Gloves(piece) => Some(("gloves", piece)),
+            Self::Knife(piece) => Some(("knife", piece)),
+            Self::Lead(piece) => Some(("lead", piece)),
+            Self::Light(piece) => Some(("light", piece)),
+            Self::Mask(piece) => Some(("mask", piece)),
+            Self::Rebreather(piece) => Some(("rebreather", piece)),
+            Self::Regulator(piece) => Some(("regulator", piece)),
+            Self::Scooter(piece) => Some(("scooter", piece)),
+            Self::Suit(piece) => Some(("suit", piece)),
+            Self::Tank(piece) => Some(("tank", piece)),
+            Self::VariousPieces(piece) => Some(("variouspieces", piece)),
+            Self::VideoCamera(piece) => Some(("videocamera", piece)),
+            Self::Watch(piece) => Some(("watch", piece)),
+            Self::Other => None,
+        }
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PieceElem {
+    #[serde(rename = "@id")]
+    pub id: String,
+    pub name: Option<String>,
+    pub manufacturer: Option<ManufacturerElem>,
+    pub model: Option<String>,
+    #[serde(rename = "serialnumber")]
+    pub serial_number: Option<String>,
+    pub purchase: Option<DateTimeElem>,
+    // days
+    #[serde(rename = "serviceinterval")]
+    pub service_interval: Option<u32>,
+    #[serde(rename = "nextservicedate")]
+    pub next_service_date: Option<DateTimeElem>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ManufacturerElem {
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DateTimeElem {
+    #[serde(rename = "datetime")]
+    pub date_time: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiveSiteElem {
+    #[serde(rename = "site")]
+    pub sites: Option<Vec<SiteElem>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SiteElem {
+    #[serde(rename = "@id")]
+    pub id: String,
+    pub name: Option<String>,
+    pub geography: Option<GeographyElem>,
+    // "ocean-sea", "lake-quarry", "river-spring", "pool" and others
+    pub environment: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GeographyElem {
+    pub location: Option<String>,
+    // degrees
+    pub latitude: Option<f64>,
+    pub longitude: Option<f64>,
+    // meters
+    pub altitude: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DecoModelElem {
+    #[serde(rename = "buehlmann")]
+    pub buehlmann: Option<Vec<BuehlmannElem>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BuehlmannElem {
+    #[serde(rename = "@id")]
+    pub id: Option<String>,
+    #[serde(rename = "gradientfactorlow")]
+    pub gradient_factor_low: Option<f64>,
+    #[serde(rename = "gradientfactorhigh")]
+    pub gradient_factor_high: Option<f64>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -29,40 +219,111 @@ pub struct Mix {
     pub he: Option<f64>,
 }
 
-#[derive(Deserialize)]
-pub struct ProfileDataElem {
-    #[serde(rename = "repetitiongroup")]
-    pub repetition_group: Vec<RepetitionGroupElem>,
+#[derive(Debug, Deserialize)]
+pub struct DiveElem {
+    pub samples: Option<SampleElem>,
+    #[serde(rename = "informationbeforedive")]
+    pub information_before_dive: InfoElem,
+    #[serde(rename = "tankdata")]
+    pub tank_data: Option<Vec<TankDataElem>>,
+    #[serde(rename = "informationafterdive")]
+    pub information_after_dive: Option<InfoAfterElem>,
 }
 
-#[derive(Deserialize)]
-pub struct RepetitionGroupElem {
-    #[serde(rename = "dive")]
-    pub dives: Vec<DiveElem>,
+#[derive(Debug, Deserialize)]
+pub struct InfoElem {
+    #[serde(rename = "datetime")]
+    pub date_time: Option<String>,
+    #[serde(rename = "surfaceintervalbeforedive")]
+    pub surface_interval_before_dive: Option<SurfaceIntervalElem>,
+    // Pascal, read as text so invalid value falls back to configured one
+    #[serde(rename = "surfacepressure")]
+    pub surface_pressure: Option<String>,
+    // water density in kg/m3
+    pub density: Option<String>,
+    // deco model, site, buddies and other references
+    #[serde(rename = "link")]
+    pub links: Option<Vec<LinkElem>>,
+    #[serde(rename = "divenumber")]
+    pub dive_number: Option<u32>,
+    // Kelvin
+    #[serde(rename = "airtemperature")]
+    pub air_temperature: Option<f64>,
+    #[serde(rename = "equipmentused")]
+    pub equipment_used: Option<EquipmentUsedElem>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct DiveElem {
-    pub samples: SampleElem,
-    #[serde(rename = "informationbeforedive")]
-    pub information_before_dive: InfoElem,
+pub struct EquipmentUsedElem {
+    // equipment pieces
+    #[serde(rename = "link")]
+    pub links: Option<Vec<LinkElem>>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct InformationBeforeDiveElem {
-    #[serde(rename = "informationbeforedive")]
-    pub information_before_dive: InfoElem,
+pub struct InfoAfterElem {
+    #[serde(rename = "greatestdepth")]
+    pub greatest_depth: Option<Depth>,
+    #[serde(rename = "averagedepth")]
+    pub average_depth: Option<Depth>,
+    // seconds
+    #[serde(rename = "diveduration")]
+    pub dive_duration: Option<f64>,
+    // Kelvin
+    #[serde(rename = "lowesttemperature")]
+    pub lowest_temperature: Option<f64>,
+    // meters
+    pub visibility: Option<f64>,
+    pub notes: Option<NotesElem>,
+    #[serde(rename = "rating")]
+    pub ratings: Option<Vec<RatingElem>>,
 }
 
 #[derive(Debug, Deserialize)]
-pub struct InfoElem {
-    #[serde(rename = "surfacepressure")]
-    pub surface_pressure: Option<f32>,
+pub struct NotesElem {
+    pub para: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RatingElem {
+    // 1-10
+    #[serde(rename = "ratingvalue")]
+    pub rating_value: Option<u8>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SurfaceIntervalElem {
+    // seconds, element missing if interval is infinite
+    #[serde(rename = "passedtime")]
+    pub passed_time: Option<f64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LinkElem {
+    #[serde(rename = "@ref")]
+    pub link_ref: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TankDataElem {
+    #[serde(rename = "@id")]
+    pub id: Option<String>,
+    #[serde(rename = "link")]
+    pub links: Option<Vec<LinkElem>>,
+    // cubic meters
+    #[serde(rename = "tankvolume")]
+    pub tank_volume: Option<f64>,
+    // Pascal
+    #[serde(rename = "tankpressurebegin")]
+    pub tank_pressure_begin: Option<f64>,
+    #[serde(rename = "tankpressureend")]
+    pub tank_pressure_end: Option<f64>,
 }
 
 #[derive(Debug, Deserialize)]
 pub struct SampleElem {
-    #[serde(rename = "waypoint")]
+    // empty samples element reported as dive without samples
+    #[serde(default, rename = "waypoint")]
     pub waypoints: Vec<WaypointElem>,
 }
 
@@ -74,6 +335,31 @@ pub struct WaypointElem {
     pub switchmix: Option<SwitchMix>,
     #[serde(rename = "decostop")]
     pub decostops: Option<Vec<DecostopElem>>,
+    #[serde(rename = "tankpressure")]
+    pub tank_pressures: Option<Vec<TankPressureElem>>,
+    // Kelvin
+    pub temperature: Option<f64>,
+    #[serde(rename = "divemode")]
+    pub dive_mode: Option<DiveModeElem>,
+    #[serde(rename = "setpo2")]
+    pub set_po2: Option<PartialPressureElem>,
+    // oxygen sensors
+    #[serde(rename = "measuredpo2")]
+    pub measured_po2: Option<Vec<PartialPressureElem>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DiveModeElem {
+    // "opencircuit", "closedcircuit", "semiclosedcircuit" or "apnoe"
+    #[serde(rename = "@kind")]
+    pub kind: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct PartialPressureElem {
+    // Pascal
+    #[serde(rename = "$text")]
+    pub value: f64,
 }
 
 #[derive(Debug, Deserialize)]
@@ -82,28 +368,417 @@ pub struct SwitchMix {
     pub gas_ref: String,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct TankPressureElem {
+    // tank data id, optional if only one tank used
+    #[serde(rename = "@ref")]
+    pub tank_ref: Option<String>,
+    // Pascal
+    #[serde(rename = "$text")]
+    pub value: f64,
+}
+
 #[derive(Debug, Deserialize)]
 pub struct DecostopElem {
     #[serde(rename = "@kind")]
     pub kind: String,
+    #[serde(rename = "@decodepth")]
+    pub deco_depth: Option<Depth>,
+    // seconds
+    #[serde(rename = "@duration")]
+    pub duration: Option<f64>,
+}
+
+// dive read from profile data
+pub struct ParsedDive {
+    pub repetition_group: usize,
+    pub group_index: usize,
+    // dive not matching UDDF structure, next dives still read
+    pub dive: Result<DiveElem, AnalysisError>,
+    // bytes read from file up to dive end, for progress
+    pub file_position: u64,
+}
+
+// dives read one by one from profile data, memory use bounded by a single dive,
+// iteration ends after malformed XML error
+pub struct DiveReader {
+    reader: Reader<BufReader<File>>,
+    buf: Vec<u8>,
+    in_profile_data: bool,
+    in_repetition_group: bool,
+    repetition_groups_no: usize,
+    group_index: usize,
+    finished: bool,
 }
 
-pub fn parse_file(file_path: &str) -> Result<UDDFDoc, Box<dyn Error>> {
-    let file_content = read_file_content(file_path)?;
-    let document = construct_from_uddf(&file_content)?;
+impl Iterator for DiveReader {
+    type Item = Result<ParsedDive, AnalysisError>;
 
-    Ok(document)
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        let next_dive = self.next_dive();
+        if !matches!(next_dive, Ok(Some(_))) {
+            self.finished = true;
+        }
+        next_dive.transpose()
+    }
 }
 
-fn read_file_content(path: &str) -> Result<String, io::Error> {
-    let mut file_content = String::new();
-    let mut file = fs::File::open(path)?;
-    file.read_to_string(&mut file_content)?;
+impl DiveReader {
+    // reader positioned inside profile data, nothing to read if profile data element is empty
+    fn new(reader: Reader<BufReader<File>>, in_profile_data: bool) -> Self {
+        Self {
+            reader,
+            buf: vec![],
+            in_profile_data,
+            in_repetition_group: false,
+            repetition_groups_no: 0,
+            group_index: 0,
+            finished: !in_profile_data,
+        }
+    }
 
-    Ok(file_content)
+    fn next_dive(&mut self) -> Result<Option<ParsedDive>, AnalysisError> {
+        loop {
+            self.buf.clear();
+            let element = match self.reader.read_event_into(&mut self.buf).map_err(xml_error)? {
+                Event::Start(e) => match e.local_name().as_ref() {
+                    b"repetitiongroup" if self.in_profile_data => {
+                        self.in_repetition_group = true;
+                        self.repetition_groups_no += 1;
+                        self.group_index = 0;
+                        None
+                    }
+                    b"dive" if self.in_repetition_group => {
+                        let start = e.into_owned();
+                        Some(read_element(&mut self.reader, start, &mut self.buf)?)
+                    }
+                    _ => None,
+                },
+                Event::Empty(e) => match e.local_name().as_ref() {
+                    b"repetitiongroup" if self.in_profile_data => {
+                        self.repetition_groups_no += 1;
+                        None
+                    }
+                    b"dive" if self.in_repetition_group => Some(empty_element(e)?),
+                    _ => None,
+                },
+                Event::End(e) => match e.local_name().as_ref() {
+                    // nothing else read after profile data
+                    b"profiledata" => return Ok(None),
+                    b"repetitiongroup" => {
+                        self.in_repetition_group = false;
+                        None
+                    }
+                    _ => None,
+                },
+                Event::Eof => return Ok(None),
+                _ => None,
+            };
+            if let Some(element) = element {
+                let parsed_dive = ParsedDive {
+                    repetition_group: self.repetition_groups_no - 1,
+                    group_index: self.group_index,
+                    dive: from_element(&element),
+                    file_position: self.reader.buffer_position() as u64,
+                };
+                self.group_index += 1;
+                return Ok(Some(parsed_dive));
+            }
+        }
+    }
 }
 
-fn construct_from_uddf(content: &str) -> Result<UDDFDoc, Box<dyn Error>> {
-    let document: UDDFDoc = quick_xml::de::from_str(content)?;
-    Ok(document)
+// document level definitions and dives reader, file read once,
+// definitions have to precede profile data as in UDDF specification
+pub fn parse_file(file_path: &str) -> Result<(UDDFHeader, DiveReader), AnalysisError> {
+    let mut reader = open_reader(file_path)?;
+    let (header, in_profile_data) = read_header(&mut reader)?;
+    let dives = DiveReader::new(reader, in_profile_data);
+
+    Ok((header, dives))
+}
+
+// root children before profile data read whole, other than definitions skipped,
+// reader left inside profile data element, or after it if the element is empty
+fn read_header(reader: &mut Reader<BufReader<File>>) -> Result<(UDDFHeader, bool), AnalysisError> {
+    let mut buf = vec![];
+    let mut skip_buf = vec![];
+    let mut in_root = false;
+    let mut gas_definitions: Option<GasDefinition> = None;
+    let mut deco_model: Option<DecoModelElem> = None;
+    let mut diver: Option<DiverElem> = None;
+    let mut dive_site: Option<DiveSiteElem> = None;
+    let in_profile_data = loop {
+        buf.clear();
+        match reader.read_event_into(&mut buf).map_err(xml_error)? {
+            Event::Start(_) if !in_root => in_root = true,
+            Event::Start(e) => {
+                let start = e.into_owned();
+                match start.local_name().as_ref() {
+                    b"profiledata" => break true,
+                    b"gasdefinitions" => {
+                        let element = read_element(reader, start, &mut buf)?;
+                        gas_definitions = Some(from_element(&element)?);
+                    }
+                    b"decomodel" => {
+                        let element = read_element(reader, start, &mut buf)?;
+                        deco_model = Some(from_element(&element)?);
+                    }
+                    // optional details, ignored if not matching UDDF structure
+                    b"diver" => {
+                        let element = read_element(reader, start, &mut buf)?;
+                        diver = from_element(&element).ok();
+                    }
+                    b"divesite" => {
+                        let element = read_element(reader, start, &mut buf)?;
+                        dive_site = from_element(&element).ok();
+                    }
+                    _ => {
+                        skip_buf.clear();
+                        reader.read_to_end_into(start.name(), &mut skip_buf).map_err(xml_error)?;
+                    }
+                }
+            }
+            Event::Empty(e) if in_root => match e.local_name().as_ref() {
+                b"profiledata" => break false,
+                b"gasdefinitions" => gas_definitions = Some(from_element(&empty_element(e)?)?),
+                b"decomodel" => deco_model = Some(from_element(&empty_element(e)?)?),
+                _ => (),
+            },
+            Event::Eof => {
+                return Err(AnalysisError::Parse("missing field `profiledata`".to_string()))
+            }
+            _ => (),
+        }
+    };
+
+    let gas_definitions = gas_definitions
+        .ok_or(AnalysisError::Parse("missing field `gasdefinitions`".to_string()))?;
+    let header = UDDFHeader {
+        gas_definitions,
+        deco_model,
+        diver,
+        dive_site,
+    };
+    Ok((header, in_profile_data))
+}
+
+fn open_reader(file_path: &str) -> Result<Reader<BufReader<File>>, AnalysisError> {
+    let file = File::open(file_path).map_err(|e| AnalysisError::Io(e.to_string()))?;
+    Ok(Reader::from_reader(BufReader::new(file)))
+}
+
+// element started with given event copied with its content, to be deserialized alone
+fn read_element(
+    reader: &mut Reader<BufReader<File>>,
+    start: BytesStart<'static>,
+    buf: &mut Vec<u8>,
+) -> Result<Vec<u8>, AnalysisError> {
+    let mut writer = Writer::new(vec![]);
+    writer.write_event(Event::Start(start)).map_err(xml_error)?;
+    let mut depth = 1;
+    while depth > 0 {
+        buf.clear();
+        let event = reader.read_event_into(buf).map_err(xml_error)?;
+        match event {
+            Event::Start(_) => depth += 1,
+            Event::End(_) => depth -= 1,
+            Event::Eof => {
+                return Err(xml_error(quick_xml::Error::UnexpectedEof("element".to_string())))
+            }
+            _ => (),
+        }
+        writer.write_event(event).map_err(xml_error)?;
+    }
+
+    Ok(writer.into_inner())
+}
+
+fn empty_element(element: BytesStart) -> Result<Vec<u8>, AnalysisError> {
+    let mut writer = Writer::new(vec![]);
+    writer.write_event(Event::Empty(element)).map_err(xml_error)?;
+
+    Ok(writer.into_inner())
+}
+
+fn from_element<T: DeserializeOwned>(element: &[u8]) -> Result<T, AnalysisError> {
+    quick_xml::de::from_reader(element).map_err(|e| AnalysisError::Parse(e.to_string()))
+}
+
+fn xml_error(error: quick_xml::Error) -> AnalysisError {
+    match error {
+        quick_xml::Error::Io(e) => AnalysisError::Io(e.to_string()),
+        quick_xml::Error::UnexpectedEof(_) => {
+            AnalysisError::Parse("unexpected end of file".to_string())
+        }
+        e => AnalysisError::Parse(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const GAS_DEFINITIONS: &str =
+        "<gasdefinitions><mix id=\"air\"><name>Air</name><o2>0.21</o2></mix></gasdefinitions>";
+
+    fn dive(time: Seconds) -> String {
+        format!(
+            "<dive><informationbeforedive/><samples>\
+            <waypoint><divetime>0</divetime><depth>0</depth></waypoint>\
+            <waypoint><divetime>{time}</divetime><depth>10</depth></waypoint>\
+            </samples></dive>"
+        )
+    }
+
+    // document written to a file named after the test, to be read with file reader
+    fn parse(name: &str, content: &str) -> Result<(UDDFHeader, DiveReader), AnalysisError> {
+        let path = std::env::temp_dir().join(format!("dive-reporter-parser-{name}.uddf"));
+        std::fs::write(&path, format!("<?xml version=\"1.0\"?><uddf>{content}</uddf>")).unwrap();
+        parse_file(&path.to_string_lossy())
+    }
+
+    // repetition group, index in group and last sample time, none if dive is invalid
+    type ReadDive = Result<(usize, usize, Option<Seconds>), AnalysisError>;
+
+    fn read_dives(dives: DiveReader) -> Vec<ReadDive> {
+        dives
+            .map(|parsed_dive| {
+                parsed_dive.map(|parsed_dive| {
+                    let last_time = parsed_dive.dive.ok().and_then(|dive| {
+                        Some(dive.samples?.waypoints.last()?.dive_time)
+                    });
+                    (parsed_dive.repetition_group, parsed_dive.group_index, last_time)
+                })
+            })
+            .collect()
+    }
+
+    #[test]
+    fn reports_file_position_at_dive_end() {
+        let content = format!(
+            "{GAS_DEFINITIONS}<profiledata><repetitiongroup>{}{}</repetitiongroup>\
+            <repetitiongroup>{}</repetitiongroup></profiledata>",
+            dive(60),
+            dive(120),
+            dive(180)
+        );
+        let (_, dives) = parse("positions", &content).unwrap();
+        let positions: Vec<u64> = dives.map(|dive| dive.unwrap().file_position).collect();
+        let document = format!("<?xml version=\"1.0\"?><uddf>{content}</uddf>");
+        let dive_ends: Vec<u64> = document
+            .match_indices("</dive>")
+            .map(|(index, end_tag)| (index + end_tag.len()) as u64)
+            .collect();
+        assert_eq!(positions, dive_ends);
+    }
+
+    #[test]
+    fn reads_dives_by_repetition_group() {
+        let content = format!(
+            "<generator><name>test</name></generator>{GAS_DEFINITIONS}<profiledata>\
+            <repetitiongroup>{}{}</repetitiongroup><repetitiongroup/>\
+            <repetitiongroup>{}</repetitiongroup></profiledata>",
+            dive(60),
+            dive(120),
+            dive(180)
+        );
+        let (header, dives) = parse("groups", &content).unwrap();
+        assert_eq!(header.gas_definitions.gas_mixes.unwrap().len(), 1);
+        assert_eq!(
+            read_dives(dives),
+            vec![
+                Ok((0, 0, Some(60))),
+                Ok((0, 1, Some(120))),
+                Ok((2, 0, Some(180))),
+            ]
+        );
+    }
+
+    #[test]
+    fn reads_dives_after_invalid_dive() {
+        let invalid_depth = "<dive><informationbeforedive/><samples>\
+            <waypoint><divetime>0</divetime><depth>deep</depth></waypoint>\
+            </samples></dive>";
+        let content = format!(
+            "{GAS_DEFINITIONS}<profiledata><repetitiongroup>\
+            {invalid_depth}<dive/>{}</repetitiongroup></profiledata>",
+            dive(60)
+        );
+        let (_, dives) = parse("invalid_dive", &content).unwrap();
+        assert_eq!(
+            read_dives(dives),
+            vec![Ok((0, 0, None)), Ok((0, 1, None)), Ok((0, 2, Some(60)))]
+        );
+    }
+
+    #[test]
+    fn ends_reading_after_malformed_xml() {
+        let content = format!(
+            "{GAS_DEFINITIONS}<profiledata><repetitiongroup>{}\
+            <dive><samples></dive>{}</repetitiongroup></profiledata>",
+            dive(60),
+            dive(120)
+        );
+        let (_, dives) = parse("malformed", &content).unwrap();
+        let dives = read_dives(dives);
+        assert_eq!(dives.len(), 2);
+        assert_eq!(dives[0], Ok((0, 0, Some(60))));
+        assert!(matches!(dives[1], Err(AnalysisError::Parse(_))));
+    }
+
+    #[test]
+    fn reads_no_dives_from_empty_profile_data() {
+        for profile_data in ["<profiledata/>", "<profiledata></profiledata>"] {
+            let content = format!("{GAS_DEFINITIONS}{profile_data}");
+            let (_, dives) = parse("empty_profile_data", &content).unwrap();
+            assert!(read_dives(dives).is_empty());
+        }
+    }
+
+    #[test]
+    fn ignores_invalid_optional_header_sections() {
+        let content = format!(
+            "<diver><owner><personal/></owner></diver>\
+            <divesite><site id=\"s1\"><geography><latitude>north</latitude></geography></site></divesite>\
+            {GAS_DEFINITIONS}<profiledata><repetitiongroup>{}</repetitiongroup></profiledata>",
+            dive(60)
+        );
+        let (header, dives) = parse("optional_sections", &content).unwrap();
+        assert!(header.diver.is_none());
+        assert!(header.dive_site.is_none());
+        assert_eq!(read_dives(dives), vec![Ok((0, 0, Some(60)))]);
+    }
+
+    #[test]
+    fn requires_definitions_before_profile_data() {
+        let content = format!(
+            "<profiledata><repetitiongroup>{}</repetitiongroup></profiledata>",
+            dive(60)
+        );
+        let error = parse("missing_gas_definitions", &content).err();
+        assert_eq!(
+            error,
+            Some(AnalysisError::Parse("missing field `gasdefinitions`".to_string()))
+        );
+        let content = format!("<profiledata/>{GAS_DEFINITIONS}");
+        assert!(parse("late_gas_definitions", &content).is_err());
+        let error = parse("missing_profile_data", GAS_DEFINITIONS).err();
+        assert_eq!(
+            error,
+            Some(AnalysisError::Parse("missing field `profiledata`".to_string()))
+        );
+        let content = "<gasdefinitions><mix/></gasdefinitions><profiledata/>";
+        let error = parse("invalid_gas_definitions", content).err();
+        assert!(matches!(error, Some(AnalysisError::Parse(_))));
+    }
+
+    #[test]
+    fn reports_missing_file() {
+        let error = parse_file("/nonexistent/logbook.uddf").err();
+        assert!(matches!(error, Some(AnalysisError::Io(_))));
+    }
 }
@@ -1,10 +1,25 @@
-use crate::common::{Depth, Seconds, GF};
-use crate::dive::{Dive, DiveConfig};
-use crate::parser::{self, DiveElem, Mix, UDDFDoc, WaypointElem};
+use crate::common::{
+    self, Average, Density, Depth, GradientFactorsSetting, MbarPressure, Seconds, Temperature,
+    TemperatureUnit, Timestamp, VerticalSpeed, GF,
+};
+use crate::consumption::{ConsumptionStats, GasConsumption};
+use crate::dive::{
+    CeilingViolation, Dive, DiveConfig, DiveInfo, Equipment, GasWarning, GasWarningKind, Person,
+    Site, TissuesState,
+};
+use crate::error::{AnalysisError, Skipped};
+use crate::ox_tox::{Cns, Otu};
+use crate::parser::{
+    self, BuehlmannElem, DiveElem, DiveReader, DiverElem, Mix, ParsedDive, SiteElem,
+};
 use colored::*;
+use dive_deco::{Minutes, Pressure};
 use rayon::prelude::*;
+use std::cmp::Ordering;
+use std::iter::Peekable;
+use std::sync::atomic::{AtomicBool, AtomicU64, AtomicUsize, Ordering as AtomicOrdering};
 use std::sync::{Arc, Mutex};
-use std::{error::Error, fmt::format, fs, path::PathBuf};
+use std::{error::Error, fs, path::PathBuf};
 
 #[derive(Clone, Debug, Default)]
 pub struct StatsData {
@@ -17,72 +32,1002 @@ pub struct StatsData {
     pub gf_99_max: GF,
     pub gf_end_max: GF,
     pub time_below: TimeBelowDepthData,
+    pub cns_max: Cns,
+    pub otu_total: Otu,
+    // OTU by day ("YYYY-MM-DD"), kept sorted by day, dives without date not included
+    pub otu_per_day: Vec<(String, Otu)>,
+    // gas consumption averages from dives with tank data
+    pub consumption: ConsumptionStats,
+    pub ascent_rate_max: VerticalSpeed,
+    pub descent_rate_max: VerticalSpeed,
+    // dives with ascent rate above limit
+    pub fast_ascent_dives_no: usize,
+    pub fast_ascent_time: Seconds,
+    pub tts_max: Minutes,
+    pub ceiling_max: Depth,
+    // dives with depth above deco ceiling
+    pub ceiling_violation_dives_no: usize,
+    pub ceiling_violations_no: usize,
+    // dives with gas breathed outside ppO2 limits or counterdiffusion switches
+    pub gas_warning_dives_no: usize,
+    pub hypoxic_warnings_no: usize,
+    pub mod_warnings_no: usize,
+    pub counterdiffusion_warnings_no: usize,
+    // dives with time on rebreather loop
+    pub loop_dives_no: usize,
+    pub loop_time: Seconds,
+    pub open_circuit_time: Seconds,
+    pub bailouts_no: usize,
+    // coldest dive lowest water temperature
+    pub temperature_min: Option<Temperature>,
+    pub temperature_min_date: Option<String>,
+    // average of dives average water temperature
+    pub temperature_avg: Average,
+    // by month ("YYYY-MM"), kept sorted by month
+    pub temperature_per_month: Vec<(String, Average)>,
+    // by site full name, kept sorted by name
+    pub sites: Vec<(String, SiteStats)>,
+    // logbook owners by name, kept sorted by name
+    pub divers: Vec<(String, Person)>,
+    // by buddy name, kept sorted by name
+    pub buddies: Vec<(String, BuddyStats)>,
+    // by equipment label, kept sorted by label
+    pub equipment: Vec<(String, EquipmentStats)>,
+    pub dives: Vec<Dive>,
+    // files and dives excluded from stats with reasons
+    pub skipped: Vec<Skipped>,
+    // analysed files, including ones with all dives skipped or filtered out, kept sorted
+    pub sources: Vec<String>,
+}
+
+impl StatsData {
+    pub fn sorted_dives(&self, sort_key: DiveSortKey, descending: bool) -> Vec<&Dive> {
+        self.sorted_dive_indices(sort_key, descending)
+            .into_iter()
+            .map(|i| &self.dives[i])
+            .collect()
+    }
+
+    // positions of dives in sort order, for views keeping the order between updates
+    pub fn sorted_dive_indices(&self, sort_key: DiveSortKey, descending: bool) -> Vec<usize> {
+        let mut indices: Vec<usize> = (0..self.dives.len()).collect();
+        indices.sort_by(|a, b| {
+            let ordering = Stats::compare_dives(&self.dives[*a], &self.dives[*b], sort_key);
+            match descending {
+                true => ordering.reverse(),
+                false => ordering,
+            }
+        });
+        indices
+    }
+
+    // invalid dive data replaced with configured values, in logbook order
+    pub fn warnings(&self) -> Vec<String> {
+        self.dives
+            .iter()
+            .flat_map(|dive| {
+                dive.warnings.iter().map(|warning| {
+                    format!(
+                        "{} (group {} dive {}): {warning}",
+                        dive.source,
+                        dive.repetition_group + 1,
+                        dive.group_index + 1
+                    )
+                })
+            })
+            .collect()
+    }
+
+    // dive stats added to aggregates
+    pub fn add_dive(&mut self, dive: Dive) {
+        // dives no
+        self.dives_no += 1;
+        // time
+        self.total_time += dive.total_time;
+        // depth
+        if dive.depth_max > self.depth_max {
+            self.depth_max = dive.depth_max;
+        }
+        // time in deco
+        if dive.time_in_deco > 0 {
+            self.time_in_deco += dive.time_in_deco;
+            self.deco_dives_no += 1;
+        }
+        // GFs
+        if dive.gf_surf_max > self.gf_surf_max {
+            self.gf_surf_max = dive.gf_surf_max;
+        }
+        if dive.gf_99_max > self.gf_99_max {
+            self.gf_99_max = dive.gf_99_max;
+        }
+        if dive.gf_end > self.gf_end_max {
+            self.gf_end_max = dive.gf_end;
+        }
+        // time below, kept sorted by depth
+        for (treshold_depth, treshold_time) in &dive.time_below {
+            common::add_sorted(
+                &mut self.time_below,
+                *treshold_depth,
+                *treshold_time,
+                Depth::total_cmp,
+                |time, dive_time| *time += dive_time,
+            );
+        }
+        // oxygen toxicity
+        if dive.cns_max > self.cns_max {
+            self.cns_max = dive.cns_max;
+        }
+        self.otu_total += dive.otu;
+        if let Some(day) = dive.date.as_ref().and_then(|date| date.get(..10)) {
+            common::add_sorted(
+                &mut self.otu_per_day,
+                day.to_string(),
+                dive.otu,
+                String::cmp,
+                |otu, dive_otu| *otu += dive_otu,
+            );
+        }
+        // vertical speed
+        if dive.ascent_rate_max > self.ascent_rate_max {
+            self.ascent_rate_max = dive.ascent_rate_max;
+        }
+        if dive.descent_rate_max > self.descent_rate_max {
+            self.descent_rate_max = dive.descent_rate_max;
+        }
+        if dive.fast_ascents > 0 {
+            self.fast_ascent_dives_no += 1;
+            self.fast_ascent_time += dive.fast_ascent_time;
+        }
+        // deco timeline
+        if dive.tts_max > self.tts_max {
+            self.tts_max = dive.tts_max;
+        }
+        if dive.ceiling_max > self.ceiling_max {
+            self.ceiling_max = dive.ceiling_max;
+        }
+        // ceiling violations
+        if !dive.ceiling_violations.is_empty() {
+            self.ceiling_violation_dives_no += 1;
+            self.ceiling_violations_no += dive.ceiling_violations.len();
+        }
+        // gas consumption
+        if let Some(gas_consumption) = &dive.gas_consumption {
+            self.consumption.add(gas_consumption, dive.date.as_deref());
+        }
+        // gas warnings
+        if !dive.gas_warnings.is_empty() {
+            self.gas_warning_dives_no += 1;
+        }
+        for warning in dive.gas_warnings.iter() {
+            match warning.kind {
+                GasWarningKind::Hypoxic => self.hypoxic_warnings_no += 1,
+                GasWarningKind::AboveMod => self.mod_warnings_no += 1,
+                GasWarningKind::Counterdiffusion => self.counterdiffusion_warnings_no += 1,
+            }
+        }
+        // rebreather
+        if dive.loop_time > 0 {
+            self.loop_dives_no += 1;
+        }
+        self.loop_time += dive.loop_time;
+        self.open_circuit_time += dive.open_circuit_time;
+        self.bailouts_no += dive.bailouts.len();
+        // water temperature
+        if let Some(temperature_min) = dive.water_temperature_min() {
+            self.add_temperature_min(temperature_min, dive.date.clone());
+        }
+        if let Some(temperature_avg) = dive.temperature_avg {
+            self.temperature_avg.add(temperature_avg);
+            if let Some(month) = dive.date.as_ref().and_then(|date| date.get(..7)) {
+                let mut average = Average::default();
+                average.add(temperature_avg);
+                common::add_sorted(
+                    &mut self.temperature_per_month,
+                    month.to_string(),
+                    average,
+                    String::cmp,
+                    Average::merge,
+                );
+            }
+        }
+        // site
+        if let Some(site) = &dive.info.site {
+            common::add_sorted(
+                &mut self.sites,
+                site.full_name(),
+                SiteStats::new(site.clone(), &dive),
+                String::cmp,
+                SiteStats::merge,
+            );
+        }
+        // diver and buddies
+        if let Some(diver) = &dive.info.diver {
+            common::add_sorted(
+                &mut self.divers,
+                diver.name_readable(),
+                diver.clone(),
+                String::cmp,
+                Person::merge,
+            );
+        }
+        for buddy in dive.info.buddies.iter() {
+            common::add_sorted(
+                &mut self.buddies,
+                buddy.name_readable(),
+                BuddyStats::new(buddy.clone(), &dive),
+                String::cmp,
+                BuddyStats::merge,
+            );
+        }
+        // equipment
+        for item in dive.info.equipment.iter() {
+            common::add_sorted(
+                &mut self.equipment,
+                item.label(),
+                EquipmentStats::new(item.clone(), &dive),
+                String::cmp,
+                EquipmentStats::merge,
+            );
+        }
+        // dive record
+        self.dives.push(dive);
+    }
+
+    // aggregates combined with stats from other dives, dives and skipped in logbook order
+    // only after sorting
+    pub fn merge(mut self, other: StatsData) -> StatsData {
+        self.dives_no += other.dives_no;
+        self.total_time += other.total_time;
+        self.depth_max = self.depth_max.max(other.depth_max);
+        self.time_in_deco += other.time_in_deco;
+        self.deco_dives_no += other.deco_dives_no;
+        self.gf_surf_max = self.gf_surf_max.max(other.gf_surf_max);
+        self.gf_99_max = self.gf_99_max.max(other.gf_99_max);
+        self.gf_end_max = self.gf_end_max.max(other.gf_end_max);
+        for (treshold_depth, treshold_time) in other.time_below {
+            common::add_sorted(
+                &mut self.time_below,
+                treshold_depth,
+                treshold_time,
+                Depth::total_cmp,
+                |time, other_time| *time += other_time,
+            );
+        }
+        self.cns_max = self.cns_max.max(other.cns_max);
+        self.otu_total += other.otu_total;
+        for (day, otu) in other.otu_per_day {
+            common::add_sorted(
+                &mut self.otu_per_day,
+                day,
+                otu,
+                String::cmp,
+                |otu, other_otu| *otu += other_otu,
+            );
+        }
+        self.consumption.merge(other.consumption);
+        self.ascent_rate_max = self.ascent_rate_max.max(other.ascent_rate_max);
+        self.descent_rate_max = self.descent_rate_max.max(other.descent_rate_max);
+        self.fast_ascent_dives_no += other.fast_ascent_dives_no;
+        self.fast_ascent_time += other.fast_ascent_time;
+        self.tts_max = self.tts_max.max(other.tts_max);
+        self.ceiling_max = self.ceiling_max.max(other.ceiling_max);
+        self.ceiling_violation_dives_no += other.ceiling_violation_dives_no;
+        self.ceiling_violations_no += other.ceiling_violations_no;
+        self.gas_warning_dives_no += other.gas_warning_dives_no;
+        self.hypoxic_warnings_no += other.hypoxic_warnings_no;
+        self.mod_warnings_no += other.mod_warnings_no;
+        self.counterdiffusion_warnings_no += other.counterdiffusion_warnings_no;
+        self.loop_dives_no += other.loop_dives_no;
+        self.loop_time += other.loop_time;
+        self.open_circuit_time += other.open_circuit_time;
+        self.bailouts_no += other.bailouts_no;
+        if let Some(temperature_min) = other.temperature_min {
+            self.add_temperature_min(temperature_min, other.temperature_min_date);
+        }
+        self.temperature_avg.merge(other.temperature_avg);
+        for (month, average) in other.temperature_per_month {
+            common::add_sorted(
+                &mut self.temperature_per_month,
+                month,
+                average,
+                String::cmp,
+                Average::merge,
+            );
+        }
+        for (name, site_stats) in other.sites {
+            common::add_sorted(
+                &mut self.sites,
+                name,
+                site_stats,
+                String::cmp,
+                SiteStats::merge,
+            );
+        }
+        for (name, diver) in other.divers {
+            common::add_sorted(&mut self.divers, name, diver, String::cmp, Person::merge);
+        }
+        for (name, buddy_stats) in other.buddies {
+            common::add_sorted(
+                &mut self.buddies,
+                name,
+                buddy_stats,
+                String::cmp,
+                BuddyStats::merge,
+            );
+        }
+        for (label, equipment_stats) in other.equipment {
+            common::add_sorted(
+                &mut self.equipment,
+                label,
+                equipment_stats,
+                String::cmp,
+                EquipmentStats::merge,
+            );
+        }
+        self.dives.extend(other.dives);
+        self.skipped.extend(other.skipped);
+        for source in other.sources {
+            self.add_source(source);
+        }
+        self
+    }
+
+    fn add_source(&mut self, source: String) {
+        if let Err(i) = self.sources.binary_search(&source) {
+            self.sources.insert(i, source);
+        }
+    }
+
+    // coldest dive kept, earlier one if equal
+    fn add_temperature_min(&mut self, temperature: Temperature, date: Option<String>) {
+        let colder = match self.temperature_min {
+            Some(temperature_min) if temperature == temperature_min => {
+                date.is_some()
+                    && (self.temperature_min_date.is_none() || date < self.temperature_min_date)
+            }
+            Some(temperature_min) => temperature < temperature_min,
+            None => true,
+        };
+        if colder {
+            self.temperature_min = Some(temperature);
+            self.temperature_min_date = date;
+        }
+    }
+
+    // dives and skipped entries in logbook order
+    fn sort(&mut self) {
+        self.dives
+            .sort_by(|a, b| Stats::compare_dives(a, b, DiveSortKey::Source));
+        self.skipped
+            .sort_by(|a, b| (&a.source, a.dive).cmp(&(&b.source, b.dive)));
+    }
+
+    pub fn otu_per_day_max(&self) -> Otu {
+        self.otu_per_day
+            .iter()
+            .map(|(_, otu)| *otu)
+            .fold(0., Otu::max)
+    }
+}
+
+// dives at a site, sites with the same name and location in different files combined
+#[derive(Clone, Debug, PartialEq)]
+pub struct SiteStats {
+    pub site: Site,
+    pub dives_no: usize,
+    pub depth_max: Depth,
+    // average of dives time-weighted average depth
+    pub depth_avg: Average,
+    pub total_time: Seconds,
+    // average of dives average water temperature
+    pub temperature_avg: Average,
+    // latest dive date, none if no dive dated
+    pub last_visited: Option<String>,
+}
+
+impl SiteStats {
+    pub fn new(site: Site, dive: &Dive) -> Self {
+        let mut depth_avg = Average::default();
+        depth_avg.add(dive.depth_avg);
+        let mut temperature_avg = Average::default();
+        if let Some(temperature) = dive.temperature_avg {
+            temperature_avg.add(temperature);
+        }
+        Self {
+            site,
+            dives_no: 1,
+            depth_max: dive.depth_max,
+            depth_avg,
+            total_time: dive.total_time,
+            temperature_avg,
+            last_visited: dive.date.clone(),
+        }
+    }
+
+    // site data with smaller id kept, so that result doesn't depend on merge order
+    pub fn merge(&mut self, other: SiteStats) {
+        if other.site.partial_cmp(&self.site) == Some(Ordering::Less) {
+            self.site = other.site;
+        }
+        self.dives_no += other.dives_no;
+        self.depth_max = self.depth_max.max(other.depth_max);
+        self.depth_avg.merge(other.depth_avg);
+        self.total_time += other.total_time;
+        self.temperature_avg.merge(other.temperature_avg);
+        self.last_visited = self.last_visited.take().max(other.last_visited);
+    }
+}
+
+// dives with a buddy, buddies with the same name in different files combined
+#[derive(Clone, Debug, PartialEq)]
+pub struct BuddyStats {
+    pub buddy: Person,
+    pub dives_no: usize,
+    pub total_time: Seconds,
+    // deepest dive together
+    pub depth_max: Depth,
+    pub depth_max_date: Option<String>,
+}
+
+impl BuddyStats {
+    pub fn new(buddy: Person, dive: &Dive) -> Self {
+        Self {
+            buddy,
+            dives_no: 1,
+            total_time: dive.total_time,
+            depth_max: dive.depth_max,
+            depth_max_date: dive.date.clone(),
+        }
+    }
+
+    // earliest dated dive kept as deepest if depths are equal
+    pub fn merge(&mut self, other: BuddyStats) {
+        self.buddy.merge(other.buddy);
+        self.dives_no += other.dives_no;
+        self.total_time += other.total_time;
+        let deeper = match other.depth_max.total_cmp(&self.depth_max) {
+            Ordering::Greater => true,
+            Ordering::Equal => match (&other.depth_max_date, &self.depth_max_date) {
+                (Some(other_date), Some(date)) => other_date < date,
+                (Some(_), None) => true,
+                (None, _) => false,
+            },
+            Ordering::Less => false,
+        };
+        if deeper {
+            self.depth_max = other.depth_max;
+            self.depth_max_date = other.depth_max_date;
+        }
+    }
+}
+
+// dives with an equipment piece, pieces with the same name and serial number in different files combined
+#[derive(Clone, Debug, PartialEq)]
+pub struct EquipmentStats {
+    pub item: Equipment,
+    pub dives_no: usize,
+    pub total_time: Seconds,
+    // dated dives start, for dives since last service
+    pub dive_dates: Vec<String>,
+}
+
+impl EquipmentStats {
+    pub fn new(item: Equipment, dive: &Dive) -> Self {
+        Self {
+            item,
+            dives_no: 1,
+            total_time: dive.total_time,
+            dive_dates: dive.date.iter().cloned().collect(),
+        }
+    }
+
+    // piece data with latest next service date kept, smaller one if dates are equal
+    pub fn merge(&mut self, other: EquipmentStats) {
+        self.dives_no += other.dives_no;
+        self.total_time += other.total_time;
+        self.dive_dates.extend(other.dive_dates);
+        self.dive_dates.sort();
+        let later_service = match other
+            .item
+            .next_service_date
+            .cmp(&self.item.next_service_date)
+        {
+            Ordering::Greater => true,
+            Ordering::Equal => other.item < self.item,
+            Ordering::Less => false,
+        };
+        if later_service {
+            self.item = other.item;
+        }
+    }
+
+    pub fn last_used(&self) -> Option<&String> {
+        self.dive_dates.iter().max()
+    }
+
+    // dated dives since last service, all dives if last service unknown
+    pub fn dives_since_service(&self) -> usize {
+        match self.item.last_service_timestamp() {
+            Some(last_service) => self
+                .dive_dates
+                .iter()
+                .filter_map(|date| common::parse_timestamp(date))
+                .filter(|timestamp| *timestamp >= last_service)
+                .count(),
+            None => self.dives_no,
+        }
+    }
+
+    // next service date from equipment data, or last service with service interval in days
+    pub fn service_due_timestamp(&self, service_days: Option<u32>) -> Option<Timestamp> {
+        let next_service = self
+            .item
+            .next_service_date
+            .as_deref()
+            .and_then(common::parse_timestamp);
+        if next_service.is_some() {
+            return next_service;
+        }
+        let days = self.item.service_interval_days.or(service_days)?;
+        Some(self.item.last_service_timestamp()? + days as Timestamp * 86400)
+    }
+
+    // service date passed or dives since last service above configured interval
+    pub fn service_warnings(&self, config: &StatsConfig, now: Timestamp) -> Vec<String> {
+        let mut warnings = vec![];
+        if let Some(service_due) = self.service_due_timestamp(config.service_days) {
+            if service_due <= now {
+                warnings.push(format!(
+                    "service due since {}",
+                    common::timestamp_to_date(service_due)
+                ));
+            }
+        }
+        if let Some(service_dives) = config.service_dives {
+            let dives_since_service = self.dives_since_service();
+            if dives_since_service >= service_dives {
+                warnings.push(format!(
+                    "{dives_since_service} dive(s) since service, interval {service_dives}"
+                ));
+            }
+        }
+        warnings
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum DiveSortKey {
+    Source,
+    Number,
+    Date,
+    Duration,
+    Depth,
+    Deco,
+    GF99,
+    GFSurf,
 }
 
 #[derive(Clone, Debug)]
 pub struct Stats {
     pub stats_data: Arc<Mutex<StatsData>>,
+    pub config: StatsConfig,
+    pub progress: Arc<Progress>,
+}
+
+// loading progress, shared with thread running the analysis
+#[derive(Debug, Default)]
+pub struct Progress {
+    pub files_total: AtomicUsize,
+    pub files_parsed: AtomicUsize,
+    // analysed and skipped dives
+    pub dives_analysed: AtomicUsize,
+    // file sizes, parsed bytes reported per analysed repetition group
+    pub bytes_total: AtomicU64,
+    pub bytes_parsed: AtomicU64,
+    cancelled: AtomicBool,
+}
+
+impl Progress {
+    pub fn files_total(&self) -> usize {
+        self.files_total.load(AtomicOrdering::Relaxed)
+    }
+
+    pub fn files_parsed(&self) -> usize {
+        self.files_parsed.load(AtomicOrdering::Relaxed)
+    }
+
+    pub fn dives_analysed(&self) -> usize {
+        self.dives_analysed.load(AtomicOrdering::Relaxed)
+    }
+
+    // parsed part of all files size
+    pub fn fraction(&self) -> f32 {
+        let bytes_total = self.bytes_total.load(AtomicOrdering::Relaxed);
+        match bytes_total {
+            0 => 0.,
+            _ => self.bytes_parsed.load(AtomicOrdering::Relaxed) as f32 / bytes_total as f32,
+        }
+    }
+
+    // remaining files and dives not analysed, files analysed by then kept,
+    // partially analysed ones left out until loaded again
+    pub fn cancel(&self) {
+        self.cancelled.store(true, AtomicOrdering::Relaxed);
+    }
+
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(AtomicOrdering::Relaxed)
+    }
+
+    fn reset(&self, files_total: usize, bytes_total: u64) {
+        self.files_total.store(files_total, AtomicOrdering::Relaxed);
+        self.files_parsed.store(0, AtomicOrdering::Relaxed);
+        self.dives_analysed.store(0, AtomicOrdering::Relaxed);
+        self.bytes_total.store(bytes_total, AtomicOrdering::Relaxed);
+        self.bytes_parsed.store(0, AtomicOrdering::Relaxed);
+        self.cancelled.store(false, AtomicOrdering::Relaxed);
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct StatsConfig {
+    // fallback gradient factors for dives without deco model settings
+    pub gradient_factors: GradientFactorsSetting,
+    // prefer gradient factors from dive data over configured ones
+    pub use_dive_gradient_factors: bool,
+    // depths for time below depth stats
+    pub treshold_depths: Vec<Depth>,
+    // fallback surface pressure for dives without one
+    pub surface_pressure: MbarPressure,
+    // fallback water density (kg/m3) for dives without density or site water,
+    // deco model default if none
+    pub water_density: Option<Density>,
+    // ascent rate limits from depth, sorted by depth
+    pub ascent_rate_limits: AscentRateLimits,
+    // only dives with buddy matching name or id in stats, all dives if none
+    pub buddy: Option<String>,
+    // equipment service intervals, used as date interval for pieces without their own
+    pub service_dives: Option<usize>,
+    pub service_days: Option<u32>,
+    pub temperature_unit: TemperatureUnit,
+    // bar, gas MOD limit
+    pub pp_o2_max: Pressure,
+    // dives per-sample timeline and tissues replay data kept for profile view
+    // and timeline export, otherwise dropped after dive is analysed
+    pub keep_samples: bool,
+}
+
+impl Default for StatsConfig {
+    fn default() -> Self {
+        Self {
+            gradient_factors: (30, 70),
+            use_dive_gradient_factors: true,
+            treshold_depths: vec![10., 20., 30., 40.],
+            surface_pressure: common::DEFAULT_SURFACE_PRESSURE,
+            water_density: None,
+            ascent_rate_limits: vec![(0., 9.)],
+            buddy: None,
+            service_dives: None,
+            service_days: None,
+            temperature_unit: TemperatureUnit::Celsius,
+            pp_o2_max: 1.6,
+            keep_samples: false,
+        }
+    }
 }
 
 pub type StatsOutput = Vec<(String, String)>;
 
 pub type TimeBelowDepthData = Vec<(Depth, Seconds)>;
 
+pub type AscentRateLimits = Vec<(Depth, VerticalSpeed)>;
+
 pub type GasMixesData = Option<Vec<Mix>>;
 
+pub type DecoModelsData = Option<Vec<BuehlmannElem>>;
+
+pub type SitesData = Vec<SiteElem>;
+
+pub type DiverData = Option<DiverElem>;
+
+// document level definitions used by dives
 pub struct UDDFData {
     gas_mixes: GasMixesData,
-    dives_data: Vec<DiveElem>,
+    deco_models: DecoModelsData,
+    sites: SitesData,
+    diver: DiverData,
+}
+
+impl Default for Stats {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Stats {
     pub fn new() -> Self {
+        Self::with_config(StatsConfig::default())
+    }
+
+    pub fn with_config(config: StatsConfig) -> Self {
         Self {
             stats_data: Arc::new(Mutex::new(StatsData::default())),
+            config,
+            progress: Arc::new(Progress::default()),
         }
     }
 
     pub fn from_path(&self, path: &str) -> Result<Self, Box<dyn Error>> {
-        let mut stats = Self::new();
-        let path_meta = fs::metadata(path)?;
-        if path_meta.is_file() {
-            println!("File: {}", path);
-            stats.from_file(path)?;
-        } else if path_meta.is_dir() {
-            println!("Directory: {}", path);
-            stats.from_dir(path)?;
-        } else {
-            return Err("Unable to resolve file or directory".into());
-        }
-        stats.print_to_console();
+        let stats = Self::with_config(self.config.clone());
+        stats.load_paths(&[path.to_string()])?;
         Ok(stats)
     }
 
-    fn from_file(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
-        let UDDFData {
-            dives_data,
-            gas_mixes,
-        } = self.extract_data_from_file(path)?;
-        dives_data.par_iter().for_each(|dd| {
-            let dive = self.calc_dive_stats(&dd, &gas_mixes).unwrap();
-            self.update_with_dive_data(dive);
-        });
+    // analyse files and directories into own stats data, updated once all files are analysed,
+    // files analysed before skipped, progress and cancellation through shared progress
+    pub fn load_paths(&self, paths: &[String]) -> Result<(), Box<dyn Error>> {
+        let mut file_paths: Vec<PathBuf> = vec![];
+        for path in paths {
+            file_paths.append(&mut Self::resolve_uddf_paths(path)?);
+        }
+        file_paths.sort();
+        file_paths.dedup();
+        let loaded_sources = self.loaded_sources();
+        file_paths.retain(|path| !loaded_sources.contains(&path.to_string_lossy().to_string()));
+        let bytes_total = file_paths
+            .iter()
+            .filter_map(|path| fs::metadata(path).ok())
+            .map(|metadata| metadata.len())
+            .sum();
+        self.progress.reset(file_paths.len(), bytes_total);
+        // files analysed in parallel without sharing stats data, file stats merged
+        // in logbook order so that results are the same in every run
+        let loaded_stats = file_paths
+            .par_iter()
+            .map(|path| {
+                let file_stats = self.file_stats(&path.to_string_lossy());
+                self.progress
+                    .files_parsed
+                    .fetch_add(1, AtomicOrdering::Relaxed);
+                file_stats
+            })
+            .reduce(StatsData::default, StatsData::merge);
+        let mut stats_data = self.stats_data.lock().unwrap();
+        *stats_data = std::mem::take(&mut *stats_data).merge(loaded_stats);
+        // files added to already loaded ones, restore logbook order
+        stats_data.sort();
         Ok(())
     }
 
-    fn from_dir(&mut self, path: &str) -> Result<(), Box<dyn Error>> {
-        let paths = Self::traverse_for_uddf(path)?;
-        paths.par_iter().for_each(|path| {
-            let UDDFData {
-                dives_data,
-                gas_mixes,
-            } = self.extract_data_from_file(path.to_str().unwrap()).unwrap();
-            dives_data.par_iter().for_each(|dd| {
-                let dive = self.calc_dive_stats(&dd, &gas_mixes).unwrap();
-                self.update_with_dive_data(dive);
+    // stats of dives in file, whole file skipped if header is invalid or XML is malformed
+    fn file_stats(&self, path: &str) -> StatsData {
+        // files not started before cancel left out, without source
+        if self.progress.is_cancelled() {
+            return StatsData::default();
+        }
+        let mut file_stats = StatsData::default();
+        file_stats.add_source(path.to_string());
+        // parsed bytes reported by repetition groups as analysed, rest of file once done
+        let file_size = fs::metadata(path).map_or(0, |metadata| metadata.len());
+        let mut reported_bytes = 0;
+        let report_rest = |reported_bytes: u64| {
+            self.progress.bytes_parsed.fetch_add(
+                file_size.saturating_sub(reported_bytes),
+                AtomicOrdering::Relaxed,
+            );
+        };
+        let skip_file = |mut file_stats: StatsData, error: AnalysisError| {
+            file_stats.skipped.push(Skipped {
+                source: path.to_string(),
+                dive: None,
+                error,
             });
+            file_stats
+        };
+        let (uddf_data, dives) = match Self::extract_data_from_file(path) {
+            Ok(extracted) => extracted,
+            Err(error) => {
+                report_rest(reported_bytes);
+                return skip_file(file_stats, error);
+            }
+        };
+        // dives streamed from file and gathered into repetition groups, each group read when
+        // a thread is free to analyse it, so groups in memory are bounded by threads,
+        // group stats merged in file order so that results don't depend on threads
+        let mut dives = dives.peekable();
+        let mut read_error = None;
+        let groups = std::iter::from_fn(|| match self.progress.is_cancelled() {
+            true => None,
+            false => Self::next_repetition_group(&mut dives),
+        })
+        .map_while(|group| group.map_err(|error| read_error = Some(error)).ok())
+        .map(|group| {
+            let group_end = group.last().map_or(0, |dive| dive.file_position);
+            let group_bytes = group_end.saturating_sub(reported_bytes);
+            reported_bytes = reported_bytes.max(group_end);
+            (group_bytes, group)
         });
-        Ok(())
+        let mut groups_stats: Vec<(usize, StatsData)> = groups
+            .enumerate()
+            .par_bridge()
+            .map(|(index, (group_bytes, group))| {
+                let group_stats = self.repetition_group_stats(path, &uddf_data, group);
+                self.progress
+                    .bytes_parsed
+                    .fetch_add(group_bytes, AtomicOrdering::Relaxed);
+                (index, group_stats)
+            })
+            .collect();
+        report_rest(reported_bytes);
+        // file partially analysed when loading was cancelled left out, without source,
+        // so that it is analysed again on next load
+        if self.progress.is_cancelled() {
+            return StatsData::default();
+        }
+        // dives analysed before XML error dropped
+        if let Some(error) = read_error {
+            return skip_file(file_stats, error);
+        }
+        groups_stats.sort_by_key(|(index, _)| *index);
+        groups_stats
+            .into_iter()
+            .fold(file_stats, |file_stats, (_, group_stats)| {
+                file_stats.merge(group_stats)
+            })
+    }
+
+    // dives read until the next one is in other repetition group
+    fn next_repetition_group(
+        dives: &mut Peekable<DiveReader>,
+    ) -> Option<Result<Vec<ParsedDive>, AnalysisError>> {
+        let first = match dives.next()? {
+            Ok(parsed_dive) => parsed_dive,
+            Err(error) => return Some(Err(error)),
+        };
+        let mut group = vec![first];
+        while let Some(Ok(next)) = dives.peek() {
+            if next.repetition_group != group[0].repetition_group {
+                break;
+            }
+            group.extend(dives.next().and_then(Result::ok));
+        }
+        Some(Ok(group))
+    }
+
+    // repetitive dives processed in order, tissues carried over between them
+    fn repetition_group_stats(
+        &self,
+        path: &str,
+        uddf_data: &UDDFData,
+        group: Vec<ParsedDive>,
+    ) -> StatsData {
+        let mut group_stats = StatsData::default();
+        let mut tissues: Option<TissuesState> = None;
+        for parsed_dive in group {
+            if self.progress.is_cancelled() {
+                break;
+            }
+            let ParsedDive {
+                repetition_group: group_no,
+                group_index,
+                dive,
+                ..
+            } = parsed_dive;
+            let previous_tissues = tissues.take();
+            let dive_stats = dive.and_then(|dd| {
+                self.calc_dive_stats(
+                    &dd,
+                    &uddf_data.gas_mixes,
+                    &uddf_data.deco_models,
+                    &uddf_data.sites,
+                    &uddf_data.diver,
+                    previous_tissues,
+                )
+            });
+            match dive_stats {
+                Ok((mut dive, dive_tissues)) => {
+                    dive.source = path.to_string();
+                    dive.repetition_group = group_no;
+                    dive.group_index = group_index;
+                    tissues = Some(dive_tissues);
+                    if !self.config.keep_samples {
+                        dive.drop_samples();
+                    }
+                    // filtered out dives analysed for tissues carried over to next ones
+                    if self.is_included(&dive) {
+                        group_stats.add_dive(dive);
+                    }
+                }
+                Err(error) => group_stats.skipped.push(Skipped {
+                    source: path.to_string(),
+                    dive: Some((group_no, group_index)),
+                    error,
+                }),
+            }
+            self.progress
+                .dives_analysed
+                .fetch_add(1, AtomicOrdering::Relaxed);
+        }
+        group_stats
+    }
+
+    // single dive analysed again from its file with samples kept, for profile and tissues
+    // views of stats without samples, earlier dives in its group analysed for tissues
+    pub fn dive_with_samples(
+        &self,
+        path: &str,
+        repetition_group: usize,
+        group_index: usize,
+    ) -> Result<Dive, AnalysisError> {
+        let (uddf_data, dives) = Self::extract_data_from_file(path)?;
+        let mut tissues: Option<TissuesState> = None;
+        for parsed_dive in dives {
+            let parsed_dive = parsed_dive?;
+            match parsed_dive.repetition_group.cmp(&repetition_group) {
+                Ordering::Less => continue,
+                Ordering::Greater => break,
+                Ordering::Equal => (),
+            }
+            let previous_tissues = tissues.take();
+            let dive_stats = parsed_dive.dive.and_then(|dd| {
+                self.calc_dive_stats(
+                    &dd,
+                    &uddf_data.gas_mixes,
+                    &uddf_data.deco_models,
+                    &uddf_data.sites,
+                    &uddf_data.diver,
+                    previous_tissues,
+                )
+            });
+            if parsed_dive.group_index == group_index {
+                let (mut dive, _) = dive_stats?;
+                dive.source = path.to_string();
+                dive.repetition_group = repetition_group;
+                dive.group_index = group_index;
+                return Ok(dive);
+            }
+            tissues = dive_stats.ok().map(|(_, dive_tissues)| dive_tissues);
+        }
+        Err(AnalysisError::DiveNotFound(repetition_group, group_index))
+    }
+
+    // analysed files, including skipped ones and ones without dives in stats
+    pub fn loaded_sources(&self) -> Vec<String> {
+        self.stats_data.lock().unwrap().sources.clone()
+    }
+
+    // drop dives and skipped entries from removed sources,
+    // stats recalculated from remaining dives without analysing them again
+    pub fn remove_sources(&self, is_removed: impl Fn(&str) -> bool) {
+        let mut stats_data = self.stats_data.lock().unwrap();
+        let StatsData {
+            dives,
+            skipped,
+            sources,
+            ..
+        } = std::mem::take(&mut *stats_data);
+        for dive in dives.into_iter().filter(|dive| !is_removed(&dive.source)) {
+            stats_data.add_dive(dive);
+        }
+        stats_data.skipped = skipped
+            .into_iter()
+            .filter(|skipped| !is_removed(&skipped.source))
+            .collect();
+        stats_data.sources = sources
+            .into_iter()
+            .filter(|source| !is_removed(source))
+            .collect();
+    }
+
+    fn resolve_uddf_paths(path: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
+        let path_meta = fs::metadata(path)?;
+        if path_meta.is_file() {
+            Ok(vec![PathBuf::from(path)])
+        } else if path_meta.is_dir() {
+            Self::traverse_for_uddf(path)
+        } else {
+            Err("Unable to resolve file or directory".into())
+        }
     }
 
     fn traverse_for_uddf(path: &str) -> Result<Vec<PathBuf>, Box<dyn Error>> {
@@ -92,11 +1037,11 @@ impl Stats {
             let entry = entry?;
             let path = entry.path();
             if path.is_dir() {
-                let mut traversal_res = Stats::traverse_for_uddf(path.to_str().unwrap())?;
+                let mut traversal_res = Stats::traverse_for_uddf(&path.to_string_lossy())?;
                 uddf_file_paths.append(&mut traversal_res);
             }
             let extension = path.extension().unwrap_or_default();
-            if extension.to_ascii_lowercase() == "uddf" {
+            if extension.eq_ignore_ascii_case("uddf") {
                 uddf_file_paths.push(path);
             }
         }
@@ -104,87 +1049,174 @@ impl Stats {
         Ok(uddf_file_paths)
     }
 
-    fn extract_data_from_file(&self, path: &str) -> Result<UDDFData, Box<dyn Error>> {
+    fn extract_data_from_file(path: &str) -> Result<(UDDFData, DiveReader), AnalysisError> {
         // println!("Extracting dives from UDDF");
-        let file = parser::parse_file(path)?;
+        let (header, dives) = parser::parse_file(path)?;
 
-        let gas_definitions = file.gas_definitions;
-        let mut dives: Vec<DiveElem> = vec![];
-        let repetition_groups = file.profile_data.repetition_group;
-        for mut group in repetition_groups {
-            dives.append(&mut group.dives);
-        }
+        let gas_definitions = header.gas_definitions;
 
-        Ok(UDDFData {
+        let uddf_data = UDDFData {
             gas_mixes: gas_definitions.gas_mixes,
-            dives_data: dives,
-        })
+            deco_models: header
+                .deco_model
+                .and_then(|deco_model| deco_model.buehlmann),
+            sites: header
+                .dive_site
+                .and_then(|dive_site| dive_site.sites)
+                .unwrap_or_default(),
+            diver: header.diver,
+        };
+        Ok((uddf_data, dives))
     }
 
     fn calc_dive_stats(
         &self,
         dive_data: &DiveElem,
         gas_mixes: &GasMixesData,
-    ) -> Result<Dive, Box<dyn Error>> {
-        // todo: set gradient factors from dive data with default fallback
-        let tmp_init_gf = (30, 70);
-        let tmp_treshold_depths: Vec<Depth> = vec![10., 20., 30., 40.];
+        deco_models: &DecoModelsData,
+        sites: &SitesData,
+        diver: &DiverData,
+        previous_tissues: Option<TissuesState>,
+    ) -> Result<(Dive, TissuesState), AnalysisError> {
+        let gradient_factors = match self.config.use_dive_gradient_factors {
+            true => Self::dive_gradient_factors(dive_data, deco_models)
+                .unwrap_or(self.config.gradient_factors),
+            false => self.config.gradient_factors,
+        };
+        // invalid surface pressure in dive data replaced with configured one
+        let (surface_pressure, surface_pressure_warning) =
+            match self.dive_surface_pressure(dive_data) {
+                Ok(surface_pressure) => (surface_pressure, None),
+                Err(error) => (self.config.surface_pressure, Some(error)),
+            };
         let mut dive = Dive::new(DiveConfig {
-            gradient_factors: tmp_init_gf,
-            treshold_depths: tmp_treshold_depths,
+            gradient_factors,
+            surface_pressure,
+            water_density: self.dive_water_density(dive_data, sites),
+            treshold_depths: self.config.treshold_depths.clone(),
+            ascent_rate_limits: self.config.ascent_rate_limits.clone(),
+            pp_o2_max: self.config.pp_o2_max,
         });
-        dive.calc_dive_stats(dive_data, gas_mixes);
-        Ok(dive)
+        let tissues = dive.calc_dive_stats(dive_data, gas_mixes, previous_tissues)?;
+        dive.info = DiveInfo::new(dive_data, sites, diver.as_ref());
+        dive.warnings.extend(surface_pressure_warning);
+        Ok((dive, tissues))
     }
 
-    fn update_with_dive_data(&self, dive: Dive) {
-        let stats_data_arc = Arc::clone(&self.stats_data);
-        let mut stats_data = stats_data_arc.lock().unwrap();
-
-        // dives no
-        stats_data.dives_no += 1;
-        // time
-        stats_data.total_time += dive.total_time;
-        // depth
-        if dive.depth_max > stats_data.depth_max {
-            stats_data.depth_max = dive.depth_max;
+    // surface pressure from dive data (Pascal), configured one if missing
+    fn dive_surface_pressure(&self, dive_data: &DiveElem) -> Result<MbarPressure, AnalysisError> {
+        let surface_pressure = match &dive_data.information_before_dive.surface_pressure {
+            Some(surface_pressure) => surface_pressure.trim(),
+            None => return Ok(self.config.surface_pressure),
+        };
+        match surface_pressure
+            .parse::<f64>()
+            .map(|pressure| pressure / 100.)
+        {
+            Ok(pressure) if (500. ..=1500.).contains(&pressure) => {
+                Ok(pressure.round() as MbarPressure)
+            }
+            _ => Err(AnalysisError::InvalidSurfacePressure(
+                surface_pressure.to_string(),
+            )),
         }
-        // time in deco
-        if dive.time_in_deco > 0 {
-            stats_data.time_in_deco += dive.time_in_deco;
-            stats_data.deco_dives_no += 1;
+    }
+
+    // water density from dive data (kg/m3) or linked dive site environment,
+    // configured one if neither known
+    fn dive_water_density(&self, dive_data: &DiveElem, sites: &SitesData) -> Option<Density> {
+        let info = &dive_data.information_before_dive;
+        let site_density = || {
+            let links = info.links.as_deref().unwrap_or_default();
+            sites
+                .iter()
+                .find(|site| links.iter().any(|link| link.link_ref == site.id))?
+                .environment
+                .as_deref()
+                .and_then(environment_water_density)
+        };
+        info.density
+            .as_deref()
+            .and_then(parse_water_density)
+            .or_else(site_density)
+            .or(self.config.water_density)
+    }
+
+    // gradient factors from Buehlmann deco model linked to the dive,
+    // or the only one defined in a file if dive has no deco model link
+    fn dive_gradient_factors(
+        dive_data: &DiveElem,
+        deco_models: &DecoModelsData,
+    ) -> Option<GradientFactorsSetting> {
+        let deco_models = deco_models.as_ref()?;
+        let links = dive_data
+            .information_before_dive
+            .links
+            .as_deref()
+            .unwrap_or_default();
+        let linked_model = deco_models.iter().find(|model| {
+            links
+                .iter()
+                .any(|link| Some(&link.link_ref) == model.id.as_ref())
+        });
+        let deco_model = match (linked_model, deco_models.len()) {
+            (Some(model), _) => model,
+            (None, 1) => &deco_models[0],
+            _ => return None,
+        };
+        let gf_low = Self::uddf_gradient_factor(deco_model.gradient_factor_low?);
+        let gf_high = Self::uddf_gradient_factor(deco_model.gradient_factor_high?);
+        parse_gradient_factors_pair(gf_low, gf_high)
+    }
+
+    // UDDF gradient factors as fraction (0.3) or percent (30)
+    fn uddf_gradient_factor(value: f64) -> f64 {
+        match value <= 1. {
+            true => value * 100.,
+            false => value,
         }
-        // GFs
-        if dive.gf_surf_max > stats_data.gf_surf_max {
-            stats_data.gf_surf_max = dive.gf_surf_max;
-        }
-        if dive.gf_99_max > stats_data.gf_99_max {
-            stats_data.gf_99_max = dive.gf_99_max;
-        }
-        if dive.gf_end > stats_data.gf_end_max {
-            stats_data.gf_end_max = dive.gf_end;
-        }
-        // time below
-        'outer: for dive_time_below in dive.time_below {
-            let (dive_treshold_depth, dive_treshold_time) = dive_time_below;
-            for global_time_below in &mut stats_data.time_below {
-                let (global_treshold_depth, global_treshold_time) = global_time_below;
-                if dive_treshold_depth == *global_treshold_depth {
-                    global_time_below.1 += dive_treshold_time;
-                    continue 'outer;
-                }
-            }
-            stats_data
-                .time_below
-                .push((dive_treshold_depth, dive_treshold_time));
+    }
+
+    fn is_included(&self, dive: &Dive) -> bool {
+        match &self.config.buddy {
+            Some(buddy) => dive.info.has_buddy(buddy),
+            None => true,
         }
     }
 
+    fn compare_dives(a: &Dive, b: &Dive, sort_key: DiveSortKey) -> Ordering {
+        let logbook_order = (&a.source, a.repetition_group, a.group_index).cmp(&(
+            &b.source,
+            b.repetition_group,
+            b.group_index,
+        ));
+        let ordering = match sort_key {
+            DiveSortKey::Source => Ordering::Equal,
+            DiveSortKey::Number => a.info.dive_number.cmp(&b.info.dive_number),
+            DiveSortKey::Date => a.date.cmp(&b.date),
+            DiveSortKey::Duration => a.total_time.cmp(&b.total_time),
+            DiveSortKey::Depth => a.depth_max.total_cmp(&b.depth_max),
+            DiveSortKey::Deco => a.time_in_deco.cmp(&b.time_in_deco),
+            DiveSortKey::GF99 => a.gf_99_max.total_cmp(&b.gf_99_max),
+            DiveSortKey::GFSurf => a.gf_surf_max.total_cmp(&b.gf_surf_max),
+        };
+        ordering.then(logbook_order)
+    }
+
     pub fn print_to_console(&self) {
         let stats_data_arc = Arc::clone(&self.stats_data);
         let stats = stats_data_arc.lock().unwrap();
 
         println!("{}", "\n            STATS              ".underline());
+        if let Some(buddy) = &self.config.buddy {
+            println!("Dives with buddy:   {}", Self::to_colored(buddy));
+        }
+        for (_, diver) in stats.divers.iter() {
+            println!(
+                "Diver:              {}",
+                Self::to_colored(diver.name_with_certifications())
+            );
+        }
         println!("Dives:              {}", Self::to_colored(stats.dives_no));
         println!(
             "Total time:         {}",
@@ -218,24 +1250,885 @@ impl Stats {
             Self::to_colored(stats.gf_end_max.round()),
             Self::to_colored("%")
         );
-        self.print_time_below(&stats.time_below);
-    }
-
-    fn to_colored<T: std::fmt::Display>(v: T) -> ColoredString {
-        v.to_string().cyan().bold().dimmed()
-    }
-
-    fn print_time_below(&self, time_below: &TimeBelowDepthData) {
-        println!("Time below:");
-        for record in time_below.iter() {
-            let (depth, time) = record;
-            println!(
-                "  - {}m:            {}",
-                depth,
-                Self::to_colored(Self::seconds_to_readable(*time))
-            );
-        }
-    }
+        println!(
+            "Max CNS:            {}{}",
+            Self::to_colored(stats.cns_max.round()),
+            Self::to_colored("%")
+        );
+        println!(
+            "Total OTU:          {}",
+            Self::to_colored(stats.otu_total.round())
+        );
+        println!(
+            "Max OTU per day:    {}",
+            Self::to_colored(stats.otu_per_day_max().round())
+        );
+        println!(
+            "Max ascent rate:    {}",
+            Self::to_colored(format!("{:.1}m/min", stats.ascent_rate_max))
+        );
+        println!(
+            "Max descent rate:   {}",
+            Self::to_colored(format!("{:.1}m/min", stats.descent_rate_max))
+        );
+        println!(
+            "Fast ascent dives:  {}",
+            Self::to_colored(stats.fast_ascent_dives_no)
+        );
+        println!(
+            "Fast ascent time:   {}",
+            Self::to_colored(Self::seconds_to_readable(stats.fast_ascent_time))
+        );
+        println!(
+            "Max TTS:            {}",
+            Self::to_colored(format!("{}min", stats.tts_max))
+        );
+        println!(
+            "Max ceiling:        {}",
+            Self::to_colored(format!("{:.1}m", stats.ceiling_max))
+        );
+        println!(
+            "Ceiling violations: {}",
+            Self::to_colored(format!(
+                "{} in {} dive(s)",
+                stats.ceiling_violations_no, stats.ceiling_violation_dives_no
+            ))
+        );
+        println!(
+            "Gas warnings:       {}",
+            Self::to_colored(Self::gas_warnings_summary_readable(&stats))
+        );
+        println!(
+            "Loop dives:         {}",
+            Self::to_colored(stats.loop_dives_no)
+        );
+        println!(
+            "Loop / OC time:     {}",
+            Self::to_colored(format!(
+                "{} / {}",
+                Self::seconds_to_readable(stats.loop_time),
+                Self::seconds_to_readable(stats.open_circuit_time)
+            ))
+        );
+        println!(
+            "Bailouts:           {}",
+            Self::to_colored(stats.bailouts_no)
+        );
+        self.print_temperature(&stats);
+        self.print_consumption(&stats.consumption);
+        self.print_time_below(&stats.time_below);
+        self.print_sites(&stats.sites);
+        self.print_buddies(&stats.buddies);
+        self.print_equipment(&stats.equipment);
+    }
+
+    pub fn to_output(&self) -> StatsOutput {
+        let stats_data_arc = Arc::clone(&self.stats_data);
+        let stats = stats_data_arc.lock().unwrap();
+
+        let mut output: StatsOutput = vec![
+            ("dives".to_string(), stats.dives_no.to_string()),
+            ("total_time_s".to_string(), stats.total_time.to_string()),
+            ("depth_max_m".to_string(), stats.depth_max.to_string()),
+            ("deco_dives".to_string(), stats.deco_dives_no.to_string()),
+            ("time_in_deco_s".to_string(), stats.time_in_deco.to_string()),
+            (
+                "gf_surf_max".to_string(),
+                stats.gf_surf_max.round().to_string(),
+            ),
+            ("gf_99_max".to_string(), stats.gf_99_max.round().to_string()),
+            (
+                "gf_end_max".to_string(),
+                stats.gf_end_max.round().to_string(),
+            ),
+            ("cns_max".to_string(), stats.cns_max.round().to_string()),
+            ("otu_total".to_string(), stats.otu_total.round().to_string()),
+            (
+                "ascent_rate_max_m_min".to_string(),
+                format!("{:.1}", stats.ascent_rate_max),
+            ),
+            (
+                "descent_rate_max_m_min".to_string(),
+                format!("{:.1}", stats.descent_rate_max),
+            ),
+            (
+                "fast_ascent_dives".to_string(),
+                stats.fast_ascent_dives_no.to_string(),
+            ),
+            (
+                "fast_ascent_time_s".to_string(),
+                stats.fast_ascent_time.to_string(),
+            ),
+            ("tts_max_min".to_string(), stats.tts_max.to_string()),
+            (
+                "ceiling_max_m".to_string(),
+                format!("{:.1}", stats.ceiling_max),
+            ),
+            (
+                "ceiling_violation_dives".to_string(),
+                stats.ceiling_violation_dives_no.to_string(),
+            ),
+            (
+                "ceiling_violations".to_string(),
+                stats.ceiling_violations_no.to_string(),
+            ),
+            (
+                "gas_warning_dives".to_string(),
+                stats.gas_warning_dives_no.to_string(),
+            ),
+            (
+                "hypoxic_warnings".to_string(),
+                stats.hypoxic_warnings_no.to_string(),
+            ),
+            (
+                "mod_warnings".to_string(),
+                stats.mod_warnings_no.to_string(),
+            ),
+            (
+                "counterdiffusion_warnings".to_string(),
+                stats.counterdiffusion_warnings_no.to_string(),
+            ),
+            ("loop_dives".to_string(), stats.loop_dives_no.to_string()),
+            ("loop_time_s".to_string(), stats.loop_time.to_string()),
+            (
+                "open_circuit_time_s".to_string(),
+                stats.open_circuit_time.to_string(),
+            ),
+            ("bailouts".to_string(), stats.bailouts_no.to_string()),
+        ];
+        for (depth, time) in stats.time_below.iter() {
+            output.push((format!("time_below_{depth}m_s"), time.to_string()));
+        }
+        for (day, otu) in stats.otu_per_day.iter() {
+            output.push((format!("otu_{day}"), otu.round().to_string()));
+        }
+        let consumption = &stats.consumption;
+        let averages = [
+            ("rmv_avg_l_min", consumption.rmv),
+            ("sac_avg_bar_min", consumption.sac),
+            ("rmv_descent_avg_l_min", consumption.rmv_descent),
+            ("rmv_bottom_avg_l_min", consumption.rmv_bottom),
+            ("rmv_ascent_avg_l_min", consumption.rmv_ascent),
+        ];
+        for (key, average) in averages {
+            if let Some(value) = average.value() {
+                output.push((key.to_string(), format!("{value:.2}")));
+            }
+        }
+        for (month, rmv) in consumption.rmv_per_month.iter() {
+            if let Some(rmv) = rmv.value() {
+                output.push((format!("rmv_{month}_l_min"), format!("{rmv:.2}")));
+            }
+        }
+        let unit = self.config.temperature_unit;
+        let temperature_key = |key: &str| format!("{key}_{}", unit.key());
+        if let Some(temperature_min) = stats.temperature_min {
+            output.push((
+                temperature_key("water_temperature_min"),
+                format!("{:.1}", unit.value_from_celsius(temperature_min)),
+            ));
+        }
+        if let Some(date) = &stats.temperature_min_date {
+            output.push(("water_temperature_min_date".to_string(), date.clone()));
+        }
+        if let Some(temperature_avg) = stats.temperature_avg.value() {
+            output.push((
+                temperature_key("water_temperature_avg"),
+                format!("{:.1}", unit.value_from_celsius(temperature_avg)),
+            ));
+        }
+        for (month, average) in stats.temperature_per_month.iter() {
+            if let Some(temperature) = average.value() {
+                output.push((
+                    temperature_key(&format!("water_temperature_{month}")),
+                    format!("{:.1}", unit.value_from_celsius(temperature)),
+                ));
+            }
+        }
+        for (name, site_stats) in stats.sites.iter() {
+            if let Some(temperature) = site_stats.temperature_avg.value() {
+                output.push((
+                    temperature_key(&format!("site_{name}_water_temperature_avg")),
+                    format!("{:.1}", unit.value_from_celsius(temperature)),
+                ));
+            }
+            output.push((
+                format!("site_{name}_dives"),
+                site_stats.dives_no.to_string(),
+            ));
+            output.push((
+                format!("site_{name}_depth_max_m"),
+                site_stats.depth_max.to_string(),
+            ));
+            if let Some(depth_avg) = site_stats.depth_avg.value() {
+                output.push((
+                    format!("site_{name}_depth_avg_m"),
+                    format!("{depth_avg:.1}"),
+                ));
+            }
+            output.push((
+                format!("site_{name}_total_time_s"),
+                site_stats.total_time.to_string(),
+            ));
+            if let Some(last_visited) = &site_stats.last_visited {
+                output.push((format!("site_{name}_last_visited"), last_visited.clone()));
+            }
+        }
+        for (name, diver) in stats.divers.iter() {
+            output.push((
+                format!("diver_{name}_certifications"),
+                diver.certifications.join("; "),
+            ));
+        }
+        for (name, buddy_stats) in stats.buddies.iter() {
+            output.push((
+                format!("buddy_{name}_dives"),
+                buddy_stats.dives_no.to_string(),
+            ));
+            output.push((
+                format!("buddy_{name}_total_time_s"),
+                buddy_stats.total_time.to_string(),
+            ));
+            output.push((
+                format!("buddy_{name}_depth_max_m"),
+                buddy_stats.depth_max.to_string(),
+            ));
+            if let Some(date) = &buddy_stats.depth_max_date {
+                output.push((format!("buddy_{name}_depth_max_date"), date.clone()));
+            }
+        }
+        let now = common::now_timestamp();
+        for (label, equipment_stats) in stats.equipment.iter() {
+            output.push((
+                format!("equipment_{label}_dives"),
+                equipment_stats.dives_no.to_string(),
+            ));
+            output.push((
+                format!("equipment_{label}_total_time_s"),
+                equipment_stats.total_time.to_string(),
+            ));
+            output.push((
+                format!("equipment_{label}_dives_since_service"),
+                equipment_stats.dives_since_service().to_string(),
+            ));
+            if let Some(last_service) = equipment_stats.item.last_service_timestamp() {
+                output.push((
+                    format!("equipment_{label}_last_service"),
+                    common::timestamp_to_date(last_service),
+                ));
+            }
+            if let Some(service_due) =
+                equipment_stats.service_due_timestamp(self.config.service_days)
+            {
+                output.push((
+                    format!("equipment_{label}_service_due"),
+                    common::timestamp_to_date(service_due),
+                ));
+            }
+            let warnings = equipment_stats.service_warnings(&self.config, now);
+            if !warnings.is_empty() {
+                output.push((
+                    format!("equipment_{label}_service_warnings"),
+                    warnings.join("; "),
+                ));
+            }
+        }
+        output
+    }
+
+    pub fn to_csv(&self) -> String {
+        let mut csv = String::from("stat,value\n");
+        for (key, value) in self.to_output() {
+            csv.push_str(&format!("{},{}\n", csv_field(&key), csv_field(&value)));
+        }
+        csv
+    }
+
+    // timeline of all dives in logbook order, one row per sample
+    pub fn timeline_to_csv(&self) -> String {
+        let stats_data_arc = Arc::clone(&self.stats_data);
+        let stats = stats_data_arc.lock().unwrap();
+        let unit = self.config.temperature_unit;
+        let mut csv = format!(
+            "source,group,dive,time_s,depth_m,ceiling_m,ndl_min,tts_min,gf_99,pp_o2,temperature_{}\n",
+            unit.key()
+        );
+        for dive in stats.sorted_dives(DiveSortKey::Source, false) {
+            for point in dive.timeline.iter() {
+                let optional =
+                    |value: Option<Minutes>| value.map(|v| v.to_string()).unwrap_or_default();
+                csv.push_str(&format!(
+                    "{},{},{},{},{},{:.1},{},{},{:.0},{:.2},{}\n",
+                    csv_field(&dive.source),
+                    dive.repetition_group + 1,
+                    dive.group_index + 1,
+                    point.time,
+                    point.depth,
+                    point.ceiling,
+                    optional(point.ndl),
+                    optional(point.tts),
+                    point.gf_99,
+                    point.pp_o2,
+                    point
+                        .temperature
+                        .map(|temperature| format!("{:.1}", unit.value_from_celsius(temperature)))
+                        .unwrap_or_default()
+                ));
+            }
+        }
+        csv
+    }
+
+    pub fn print_dives_to_console(&self, sort_key: DiveSortKey, descending: bool) {
+        let stats_data_arc = Arc::clone(&self.stats_data);
+        let stats = stats_data_arc.lock().unwrap();
+        let dives = stats.sorted_dives(sort_key, descending);
+        let mut last_source: Option<&str> = None;
+        println!(
+            "{}",
+            format!(
+                "{:<4} {:<17} {:>12} {:>8} {:>12} {:>6} {:>7} {:>7} {:>5} {:>5} {:>5}",
+                "",
+                "Date",
+                "Duration",
+                "Depth",
+                "Deco",
+                "GF99",
+                "SurfGF",
+                "EndGF",
+                "ppO2",
+                "CNS",
+                "OTU"
+            )
+            .underline()
+        );
+        for (i, dive) in dives.iter().enumerate() {
+            // group by file when listed in logbook order
+            if sort_key == DiveSortKey::Source && last_source != Some(&dive.source) {
+                println!("{}", dive.source.dimmed());
+                last_source = Some(&dive.source);
+            }
+            println!(
+                "{:<4} {:<17} {:>12} {:>7}m {:>12} {:>5}% {:>6}% {:>6}% {:>5.2} {:>4}% {:>5}",
+                i + 1,
+                dive.date_readable(),
+                Self::seconds_to_readable(dive.total_time),
+                dive.depth_max,
+                Self::seconds_to_readable(dive.time_in_deco),
+                dive.gf_99_max.round(),
+                dive.gf_surf_max.round(),
+                dive.gf_end.round(),
+                dive.pp_o2_max,
+                dive.cns_max.round(),
+                dive.otu.round()
+            );
+            let mut details: Vec<String> = vec![];
+            if sort_key != DiveSortKey::Source {
+                details.push(dive.source.clone());
+            }
+            details.push(format!(
+                "group {} dive {}",
+                dive.repetition_group + 1,
+                dive.group_index + 1
+            ));
+            if dive.surface_pressure != common::DEFAULT_SURFACE_PRESSURE {
+                details.push(format!("surface pressure {}mbar", dive.surface_pressure));
+            }
+            if let Some(water_density) = dive.water_density {
+                details.push(format!("water density {water_density}kg/m3"));
+            }
+            if let Some(surface_interval) = dive.surface_interval {
+                details.push(format!(
+                    "surface interval {}",
+                    Self::seconds_to_readable(surface_interval)
+                ));
+            }
+            for (depth, time) in dive.time_below.iter().filter(|(_, time)| *time > 0) {
+                details.push(format!(
+                    "below {depth}m {}",
+                    Self::seconds_to_readable(*time)
+                ));
+            }
+            details.push(Self::deco_timeline_readable(dive));
+            details.push(Self::vertical_speed_readable(dive));
+            for violation in dive.ceiling_violations.iter() {
+                details.push(Self::ceiling_violation_readable(violation));
+            }
+            for warning in dive.gas_warnings.iter() {
+                details.push(Self::gas_warning_readable(warning));
+            }
+            for warning in dive.warnings.iter() {
+                details.push(format!("{warning}, configured value used"));
+            }
+            if let Some(gas_consumption) = &dive.gas_consumption {
+                details.push(Self::consumption_readable(gas_consumption));
+            }
+            details.extend(Self::dive_mode_readable(dive));
+            details.extend(Self::water_temperature_readable(
+                dive,
+                self.config.temperature_unit,
+            ));
+            details.append(&mut Self::dive_info_readable(
+                &dive.info,
+                self.config.temperature_unit,
+            ));
+            println!("     {}", details.join(", ").dimmed());
+        }
+    }
+
+    pub fn print_warnings_to_console(&self) {
+        let stats_data_arc = Arc::clone(&self.stats_data);
+        let stats = stats_data_arc.lock().unwrap();
+        let warnings = stats.warnings();
+        if warnings.is_empty() {
+            return;
+        }
+
+        eprintln!(
+            "{}",
+            format!(
+                "\nInvalid dive data replaced with configured values ({}):",
+                warnings.len()
+            )
+            .yellow()
+        );
+        for warning in warnings.iter() {
+            eprintln!("  - {}", warning.yellow());
+        }
+    }
+
+    pub fn print_skipped_to_console(&self) {
+        let stats_data_arc = Arc::clone(&self.stats_data);
+        let stats = stats_data_arc.lock().unwrap();
+        if stats.skipped.is_empty() {
+            return;
+        }
+
+        let files_no = stats.skipped.iter().filter(|s| s.dive.is_none()).count();
+        let dives_no = stats.skipped.len() - files_no;
+        eprintln!(
+            "{}",
+            format!("\nSkipped {files_no} file(s) and {dives_no} dive(s):").yellow()
+        );
+        for skipped in stats.skipped.iter() {
+            eprintln!("  - {}", skipped.to_string().yellow());
+        }
+    }
+
+    fn to_colored<T: std::fmt::Display>(v: T) -> ColoredString {
+        v.to_string().cyan().bold().dimmed()
+    }
+
+    fn print_temperature(&self, stats: &StatsData) {
+        let unit = self.config.temperature_unit;
+        println!(
+            "Min water temp:     {}",
+            Self::to_colored(Self::temperature_min_readable(stats, unit))
+        );
+        println!(
+            "Avg water temp:     {}",
+            Self::to_colored(Self::average_temperature_readable(
+                stats.temperature_avg,
+                unit
+            ))
+        );
+        if stats.temperature_per_month.is_empty() {
+            return;
+        }
+        println!("Avg water temp by month:");
+        for (month, average) in stats.temperature_per_month.iter() {
+            println!(
+                "  - {:<16}{}",
+                format!("{month}:"),
+                Self::to_colored(Self::average_temperature_readable(*average, unit))
+            );
+        }
+    }
+
+    // coldest dive, eg. "11.0°C (2023-06-01)"
+    pub fn temperature_min_readable(stats: &StatsData, unit: TemperatureUnit) -> String {
+        let temperature_min = match stats.temperature_min {
+            Some(temperature_min) => temperature_min,
+            None => return "-".to_string(),
+        };
+        let date = match &stats.temperature_min_date {
+            Some(date) => format!(" ({})", date.get(..10).unwrap_or(date)),
+            None => String::new(),
+        };
+        format!(
+            "{}{date}",
+            Self::temperature_readable(temperature_min, unit)
+        )
+    }
+
+    pub fn average_temperature_readable(average: Average, unit: TemperatureUnit) -> String {
+        match average.value() {
+            Some(temperature) => Self::temperature_readable(temperature, unit),
+            None => "-".to_string(),
+        }
+    }
+
+    fn print_consumption(&self, consumption: &ConsumptionStats) {
+        println!(
+            "Avg RMV:            {}",
+            Self::to_colored(Self::average_readable(consumption.rmv, 1, "l/min"))
+        );
+        println!(
+            "Avg SAC:            {}",
+            Self::to_colored(Self::average_readable(consumption.sac, 2, "bar/min"))
+        );
+        println!("Avg RMV by phase:");
+        for (phase, average) in [
+            ("descent", consumption.rmv_descent),
+            ("bottom", consumption.rmv_bottom),
+            ("ascent", consumption.rmv_ascent),
+        ] {
+            println!(
+                "  - {:<16}{}",
+                format!("{phase}:"),
+                Self::to_colored(Self::average_readable(average, 1, "l/min"))
+            );
+        }
+        if consumption.rmv_per_month.is_empty() {
+            return;
+        }
+        println!("Avg RMV by month:");
+        for (month, average) in consumption.rmv_per_month.iter() {
+            println!(
+                "  - {:<16}{}",
+                format!("{month}:"),
+                Self::to_colored(Self::average_readable(*average, 1, "l/min"))
+            );
+        }
+    }
+
+    // logged details, notes last
+    pub fn dive_info_readable(info: &DiveInfo, unit: TemperatureUnit) -> Vec<String> {
+        let mut details: Vec<String> = vec![];
+        if let Some(dive_number) = info.dive_number {
+            details.push(format!("no {dive_number}"));
+        }
+        if let Some(site) = &info.site {
+            details.push(format!("site {}", site.full_name()));
+        }
+        if !info.buddies.is_empty() {
+            let buddies: Vec<String> = info.buddies.iter().map(|b| b.name_readable()).collect();
+            details.push(format!("buddies {}", buddies.join(" & ")));
+        }
+        if let Some(air_temperature) = info.air_temperature {
+            details.push(format!(
+                "air {}",
+                Self::temperature_readable(air_temperature, unit)
+            ));
+        }
+        if let Some(water_temperature) = info.water_temperature {
+            details.push(format!(
+                "logged water {}",
+                Self::temperature_readable(water_temperature, unit)
+            ));
+        }
+        if let Some(visibility) = info.visibility {
+            details.push(format!("visibility {visibility}m"));
+        }
+        if let Some(rating) = info.rating {
+            details.push(format!("rating {rating}/10"));
+        }
+        if !info.equipment.is_empty() {
+            let equipment: Vec<String> = info.equipment.iter().map(|e| e.name_readable()).collect();
+            details.push(format!("equipment {}", equipment.join(" & ")));
+        }
+        let logged: Vec<String> = [
+            info.greatest_depth.map(|depth| format!("max {depth}m")),
+            info.average_depth.map(|depth| format!("avg {depth}m")),
+            info.duration.map(Self::seconds_to_readable),
+        ]
+        .into_iter()
+        .flatten()
+        .collect();
+        if !logged.is_empty() {
+            details.push(format!("logged {}", logged.join(" ")));
+        }
+        if let Some(notes) = &info.notes {
+            details.push(format!("notes: {}", notes.replace('\n', " ")));
+        }
+        details
+    }
+
+    pub fn temperature_readable(temperature: Temperature, unit: TemperatureUnit) -> String {
+        format!(
+            "{:.1}{}",
+            unit.value_from_celsius(temperature),
+            unit.symbol()
+        )
+    }
+
+    // eg. "water min 11.0°C, avg 14.2°C, thermocline 9-12m -2.4°C"
+    pub fn water_temperature_readable(dive: &Dive, unit: TemperatureUnit) -> Option<String> {
+        let mut readable = format!(
+            "water min {}",
+            Self::temperature_readable(dive.temperature_min?, unit)
+        );
+        if let Some(temperature_avg) = dive.temperature_avg {
+            readable.push_str(&format!(
+                ", avg {}",
+                Self::temperature_readable(temperature_avg, unit)
+            ));
+        }
+        if let Some(thermocline) = &dive.thermocline {
+            readable.push_str(&format!(
+                ", thermocline {}-{}m {:+.1}{}",
+                thermocline.depth_from,
+                thermocline.depth_to,
+                unit.difference_from_celsius(thermocline.temperature_change),
+                unit.symbol()
+            ));
+        }
+        Some(readable)
+    }
+
+    pub fn average_readable(average: Average, precision: usize, unit: &str) -> String {
+        match average.value() {
+            Some(value) => format!("{value:.precision$}{unit}"),
+            None => "-".to_string(),
+        }
+    }
+
+    // eg. "max ascent 9.0m/min, max descent 18.0m/min, 1 fast ascent(s) 0h 0m 20s"
+    pub fn vertical_speed_readable(dive: &Dive) -> String {
+        let mut readable = format!(
+            "max ascent {:.1}m/min, max descent {:.1}m/min",
+            dive.ascent_rate_max, dive.descent_rate_max
+        );
+        if dive.fast_ascents > 0 {
+            readable.push_str(&format!(
+                ", {} fast ascent(s) {}",
+                dive.fast_ascents,
+                Self::seconds_to_readable(dive.fast_ascent_time)
+            ));
+        }
+        readable
+    }
+
+    // eg. "loop 0h 52m 0s, OC 0h 6m 0s, bailout at 0h 41m 20s", none for open circuit dives
+    pub fn dive_mode_readable(dive: &Dive) -> Option<String> {
+        if dive.loop_time == 0 {
+            return None;
+        }
+        let mut readable = format!(
+            "loop {}, OC {}",
+            Self::seconds_to_readable(dive.loop_time),
+            Self::seconds_to_readable(dive.open_circuit_time)
+        );
+        for bailout in dive.bailouts.iter() {
+            readable.push_str(&format!(
+                ", bailout at {}",
+                Self::seconds_to_readable(*bailout)
+            ));
+        }
+        Some(readable)
+    }
+
+    // eg. "min NDL 12min, max TTS 18min, max ceiling 6.2m"
+    pub fn deco_timeline_readable(dive: &Dive) -> String {
+        let ndl_min = match dive.ndl_min {
+            Some(ndl_min) => format!("{ndl_min}min"),
+            None => "-".to_string(),
+        };
+        format!(
+            "min NDL {ndl_min}, max TTS {}min, max ceiling {:.1}m",
+            dive.tts_max, dive.ceiling_max
+        )
+    }
+
+    // eg. "ceiling violated at 0h 31m 0s for 0h 0m 40s by 1.2m"
+    pub fn ceiling_violation_readable(violation: &CeilingViolation) -> String {
+        format!(
+            "ceiling violated at {} for {} by {:.1}m",
+            Self::seconds_to_readable(violation.start),
+            Self::seconds_to_readable(violation.duration),
+            violation.depth_difference_max
+        )
+    }
+
+    // eg. "above MOD on 50/0 at 0h 28m 20s for 0h 0m 40s, ppO2 2.05 at 31m",
+    // "counterdiffusion switch 21/0 to 18/45 at 0h 12m 0s at 40m"
+    pub fn gas_warning_readable(warning: &GasWarning) -> String {
+        let start = Self::seconds_to_readable(warning.start);
+        let duration = Self::seconds_to_readable(warning.duration);
+        match warning.kind {
+            GasWarningKind::Hypoxic => format!(
+                "hypoxic {} at {start} for {duration}, ppO2 {:.2} at {}m",
+                warning.gas, warning.pp_o2, warning.depth
+            ),
+            GasWarningKind::AboveMod => format!(
+                "above MOD on {} at {start} for {duration}, ppO2 {:.2} at {}m",
+                warning.gas, warning.pp_o2, warning.depth
+            ),
+            GasWarningKind::Counterdiffusion => format!(
+                "counterdiffusion switch {} to {} at {start} at {}m",
+                warning
+                    .previous_gas
+                    .map(|gas| gas.to_string())
+                    .unwrap_or_default(),
+                warning.gas,
+                warning.depth
+            ),
+        }
+    }
+
+    // eg. "3 in 2 dive(s) (hypoxic 0, above MOD 1, counterdiffusion 2)"
+    pub fn gas_warnings_summary_readable(stats: &StatsData) -> String {
+        format!(
+            "{} in {} dive(s) (hypoxic {}, above MOD {}, counterdiffusion {})",
+            stats.hypoxic_warnings_no + stats.mod_warnings_no + stats.counterdiffusion_warnings_no,
+            stats.gas_warning_dives_no,
+            stats.hypoxic_warnings_no,
+            stats.mod_warnings_no,
+            stats.counterdiffusion_warnings_no
+        )
+    }
+
+    // eg. "RMV 18.2l/min (descent 20.1, bottom 17.5, ascent 15.0), SAC 1.52bar/min, tank1 190-60bar 1560l"
+    pub fn consumption_readable(gas_consumption: &GasConsumption) -> String {
+        let mut readable = format!("RMV {:.1}l/min", gas_consumption.total.rmv);
+        let phases: Vec<String> = [
+            ("descent", gas_consumption.descent),
+            ("bottom", gas_consumption.bottom),
+            ("ascent", gas_consumption.ascent),
+        ]
+        .iter()
+        .filter_map(|(phase, consumption)| {
+            consumption.map(|consumption| format!("{phase} {:.1}", consumption.rmv))
+        })
+        .collect();
+        if !phases.is_empty() {
+            readable.push_str(&format!(" ({})", phases.join(", ")));
+        }
+        if let Some(sac) = gas_consumption.total.sac {
+            readable.push_str(&format!(", SAC {sac:.2}bar/min"));
+        }
+        for tank in gas_consumption.tanks.iter() {
+            readable.push_str(&format!(
+                ", {} {:.0}-{:.0}bar {:.0}l",
+                tank.id.as_deref().unwrap_or("tank"),
+                tank.pressure_begin,
+                tank.pressure_end,
+                tank.consumption.volume
+            ));
+        }
+        readable
+    }
+
+    fn print_sites(&self, sites: &[(String, SiteStats)]) {
+        if sites.is_empty() {
+            return;
+        }
+        println!("Sites:");
+        for (name, site_stats) in sites.iter() {
+            println!(
+                "  - {name}: {}",
+                Self::to_colored(Self::site_stats_readable(
+                    site_stats,
+                    self.config.temperature_unit
+                ))
+            );
+        }
+    }
+
+    pub fn site_stats_readable(site_stats: &SiteStats, unit: TemperatureUnit) -> String {
+        let last_visited = match &site_stats.last_visited {
+            Some(date) => format!(", last {}", date.get(..10).unwrap_or(date)),
+            None => String::new(),
+        };
+        let temperature = match site_stats.temperature_avg.value() {
+            Some(temperature) => {
+                format!(", water {}", Self::temperature_readable(temperature, unit))
+            }
+            None => String::new(),
+        };
+        format!(
+            "{} dive(s), max {}m, avg {}, {}{temperature}{last_visited}",
+            site_stats.dives_no,
+            site_stats.depth_max,
+            Self::average_readable(site_stats.depth_avg, 1, "m"),
+            Self::seconds_to_readable(site_stats.total_time)
+        )
+    }
+
+    fn print_buddies(&self, buddies: &[(String, BuddyStats)]) {
+        if buddies.is_empty() {
+            return;
+        }
+        println!("Buddies:");
+        for (name, buddy_stats) in buddies.iter() {
+            println!(
+                "  - {name}: {}",
+                Self::to_colored(Self::buddy_stats_readable(buddy_stats))
+            );
+        }
+    }
+
+    // eg. "12 dive(s), 9h 3m 0s, deepest 42.1m on 2023-08-02"
+    pub fn buddy_stats_readable(buddy_stats: &BuddyStats) -> String {
+        let deepest_date = match &buddy_stats.depth_max_date {
+            Some(date) => format!(" on {}", date.get(..10).unwrap_or(date)),
+            None => String::new(),
+        };
+        format!(
+            "{} dive(s), {}, deepest {}m{deepest_date}",
+            buddy_stats.dives_no,
+            Self::seconds_to_readable(buddy_stats.total_time),
+            buddy_stats.depth_max
+        )
+    }
+
+    fn print_equipment(&self, equipment: &[(String, EquipmentStats)]) {
+        if equipment.is_empty() {
+            return;
+        }
+        let now = common::now_timestamp();
+        println!("Equipment:");
+        for (label, equipment_stats) in equipment.iter() {
+            println!(
+                "  - {} {label}: {}",
+                equipment_stats.item.kind_readable(),
+                Self::to_colored(Self::equipment_stats_readable(equipment_stats, now))
+            );
+            for warning in equipment_stats.service_warnings(&self.config, now) {
+                println!("    {}", format!("⚠ {warning}").yellow());
+            }
+        }
+    }
+
+    // eg. "31 dive(s), 29h 10m 0s, last service 2023-05-02 (210 days ago), 12 dive(s) since"
+    pub fn equipment_stats_readable(equipment_stats: &EquipmentStats, now: Timestamp) -> String {
+        let mut readable = format!(
+            "{} dive(s), {}",
+            equipment_stats.dives_no,
+            Self::seconds_to_readable(equipment_stats.total_time)
+        );
+        if let Some(last_service) = equipment_stats.item.last_service_timestamp() {
+            readable.push_str(&format!(
+                ", last service {} ({} days ago), {} dive(s) since",
+                common::timestamp_to_date(last_service),
+                (now - last_service).div_euclid(86400),
+                equipment_stats.dives_since_service()
+            ));
+        }
+        readable
+    }
+
+    fn print_time_below(&self, time_below: &TimeBelowDepthData) {
+        println!("Time below:");
+        for record in time_below.iter() {
+            let (depth, time) = record;
+            println!(
+                "  - {:<16}{}",
+                format!("{depth}m:"),
+                Self::to_colored(Self::seconds_to_readable(*time))
+            );
+        }
+    }
 
     pub fn seconds_to_readable(s: Seconds) -> String {
         let seconds = s % 60;
@@ -244,3 +2137,613 @@ impl Stats {
         format!("{hours}h {minutes}m {seconds}s")
     }
 }
+
+// gradient factors setting from string, eg. "30/70" or "30,70"
+pub fn parse_gradient_factors(input: &str) -> Option<GradientFactorsSetting> {
+    let (gf_low, gf_high) = input.split_once(['/', ','])?;
+    let gf_low: f64 = gf_low.trim().parse().ok()?;
+    let gf_high: f64 = gf_high.trim().parse().ok()?;
+    parse_gradient_factors_pair(gf_low, gf_high)
+}
+
+// valid gradient factors have to be in 1-100 range, GF low not higher than GF high
+fn parse_gradient_factors_pair(gf_low: f64, gf_high: f64) -> Option<GradientFactorsSetting> {
+    let gf_range = 1. ..=100.;
+    if !gf_range.contains(&gf_low) || !gf_range.contains(&gf_high) || gf_low > gf_high {
+        return None;
+    }
+    Some((gf_low.round() as u8, gf_high.round() as u8))
+}
+
+// treshold depths from string, eg. "10,20,30"
+pub fn parse_treshold_depths(input: &str) -> Option<Vec<Depth>> {
+    let mut depths = vec![];
+    for depth in input.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+        let depth: Depth = depth.parse().ok()?;
+        if !depth.is_finite() || depth < 0. {
+            return None;
+        }
+        depths.push(depth);
+    }
+    Some(normalize_treshold_depths(depths))
+}
+
+// sorted, deduplicated treshold depths
+pub fn normalize_treshold_depths(mut depths: Vec<Depth>) -> Vec<Depth> {
+    depths.sort_by(|a, b| a.total_cmp(b));
+    depths.dedup();
+    depths
+}
+
+// ascent rate limits from string, eg. "9" or "0:9,30:18" (from depth:rate in m/min)
+pub fn parse_ascent_rate_limits(input: &str) -> Option<AscentRateLimits> {
+    let mut limits = vec![];
+    for limit in input.split(',').map(str::trim).filter(|l| !l.is_empty()) {
+        let (depth, rate) = match limit.split_once(':') {
+            Some((depth, rate)) => (depth.trim(), rate.trim()),
+            None => ("0", limit),
+        };
+        let depth: Depth = depth.parse().ok()?;
+        let rate: VerticalSpeed = rate.parse().ok()?;
+        if !depth.is_finite() || depth < 0. || !rate.is_finite() || rate <= 0. {
+            return None;
+        }
+        limits.push((depth, rate));
+    }
+    if limits.is_empty() {
+        return None;
+    }
+    Some(normalize_ascent_rate_limits(limits))
+}
+
+// sorted by depth, last limit kept for duplicated depths
+pub fn normalize_ascent_rate_limits(mut limits: AscentRateLimits) -> AscentRateLimits {
+    limits.reverse();
+    limits.sort_by(|a, b| a.0.total_cmp(&b.0));
+    limits.dedup_by(|a, b| a.0 == b.0);
+    limits
+}
+
+// limit from the deepest limit depth not deeper than given depth, none if shallower than all
+pub fn ascent_rate_limit(limits: &AscentRateLimits, depth: Depth) -> Option<VerticalSpeed> {
+    limits
+        .iter()
+        .take_while(|(limit_depth, _)| *limit_depth <= depth)
+        .last()
+        .map(|(_, rate)| *rate)
+}
+
+// quoted if containing separator, quotes or new lines
+fn csv_field(value: &str) -> String {
+    match value.contains([',', '"', '\n']) {
+        true => format!("\"{}\"", value.replace('"', "\"\"")),
+        false => value.to_string(),
+    }
+}
+
+pub fn parse_dive_sort_key(input: &str) -> Option<DiveSortKey> {
+    let sort_key = match input {
+        "file" | "source" => DiveSortKey::Source,
+        "number" | "no" => DiveSortKey::Number,
+        "date" => DiveSortKey::Date,
+        "duration" | "time" => DiveSortKey::Duration,
+        "depth" => DiveSortKey::Depth,
+        "deco" => DiveSortKey::Deco,
+        "gf99" => DiveSortKey::GF99,
+        "gfsurf" => DiveSortKey::GFSurf,
+        _ => return None,
+    };
+    Some(sort_key)
+}
+
+// ppO2 limit (bar) for gas MOD
+pub fn parse_pp_o2_max(input: &str) -> Option<Pressure> {
+    let pp_o2_max: Pressure = input.trim().parse().ok()?;
+    (1. ..=2.).contains(&pp_o2_max).then_some(pp_o2_max)
+}
+
+pub fn parse_temperature_unit(input: &str) -> Option<TemperatureUnit> {
+    match input.trim().to_lowercase().as_str() {
+        "c" | "celsius" => Some(TemperatureUnit::Celsius),
+        "f" | "fahrenheit" => Some(TemperatureUnit::Fahrenheit),
+        _ => None,
+    }
+}
+
+// water density (kg/m3) from "fresh", "salt" or density value
+pub fn parse_water_density(input: &str) -> Option<Density> {
+    match input.trim().to_lowercase().as_str() {
+        "fresh" => Some(common::FRESH_WATER_DENSITY),
+        "salt" | "sea" => Some(common::SALT_WATER_DENSITY),
+        density => {
+            let density: Density = density.parse().ok()?;
+            (900. ..=1100.).contains(&density).then_some(density)
+        }
+    }
+}
+
+// water density (kg/m3) from UDDF dive site environment, none if not known to be salt or fresh
+fn environment_water_density(environment: &str) -> Option<Density> {
+    match environment.trim() {
+        "ocean-sea" => Some(common::SALT_WATER_DENSITY),
+        "lake-quarry" | "river-spring" | "pool" => Some(common::FRESH_WATER_DENSITY),
+        _ => None,
+    }
+}
+
+// surface pressure (mbar) in deco model range
+pub fn parse_surface_pressure(input: &str) -> Option<MbarPressure> {
+    let surface_pressure: MbarPressure = input.trim().parse().ok()?;
+    (500..=1500)
+        .contains(&surface_pressure)
+        .then_some(surface_pressure)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dive_elem(xml: &str) -> DiveElem {
+        quick_xml::de::from_str(xml).unwrap()
+    }
+
+    fn mix(id: &str, o2: f64, he: Option<f64>) -> Mix {
+        Mix {
+            id: id.to_string(),
+            name: id.to_string(),
+            o2,
+            n2: None,
+            he,
+        }
+    }
+
+    fn calc_dive_stats(
+        stats: &Stats,
+        dive_data: &DiveElem,
+        gas_mixes: &GasMixesData,
+    ) -> Result<(Dive, TissuesState), AnalysisError> {
+        stats.calc_dive_stats(dive_data, gas_mixes, &None, &vec![], &None, None)
+    }
+
+    #[test]
+    fn parses_gradient_factors() {
+        assert_eq!(parse_gradient_factors("30/70"), Some((30, 70)));
+        assert_eq!(parse_gradient_factors(" 40 , 85 "), Some((40, 85)));
+        assert_eq!(parse_gradient_factors("100/100"), Some((100, 100)));
+    }
+
+    #[test]
+    fn rejects_bad_gradient_factors() {
+        assert_eq!(parse_gradient_factors("70/30"), None);
+        assert_eq!(parse_gradient_factors("0/70"), None);
+        assert_eq!(parse_gradient_factors("30/101"), None);
+        assert_eq!(parse_gradient_factors("30"), None);
+        assert_eq!(parse_gradient_factors("30/"), None);
+        assert_eq!(parse_gradient_factors("low/high"), None);
+        assert_eq!(parse_gradient_factors(""), None);
+    }
+
+    #[test]
+    fn reads_dive_gradient_factors_as_fraction_or_percent() {
+        let dive_data = dive_elem(
+            "<dive><informationbeforedive><link ref=\"gf\"/></informationbeforedive></dive>",
+        );
+        let deco_models = |gf_low, gf_high| {
+            Some(vec![BuehlmannElem {
+                id: Some("gf".to_string()),
+                gradient_factor_low: Some(gf_low),
+                gradient_factor_high: Some(gf_high),
+            }])
+        };
+        let gradient_factors = |gf_low, gf_high| {
+            Stats::dive_gradient_factors(&dive_data, &deco_models(gf_low, gf_high))
+        };
+        assert_eq!(gradient_factors(0.3, 0.7), Some((30, 70)));
+        assert_eq!(gradient_factors(30., 70.), Some((30, 70)));
+        // 1 is a fraction, 100%
+        assert_eq!(gradient_factors(0.5, 1.), Some((50, 100)));
+        assert_eq!(gradient_factors(0.8, 0.5), None);
+        assert_eq!(gradient_factors(0., 0.7), None);
+    }
+
+    #[test]
+    fn parses_surface_pressure() {
+        assert_eq!(parse_surface_pressure("1013"), Some(1013));
+        assert_eq!(parse_surface_pressure(" 500 "), Some(500));
+        assert_eq!(parse_surface_pressure("1500"), Some(1500));
+        assert_eq!(parse_surface_pressure("499"), None);
+        assert_eq!(parse_surface_pressure("1501"), None);
+        assert_eq!(parse_surface_pressure("-1"), None);
+        assert_eq!(parse_surface_pressure("1013.5"), None);
+    }
+
+    #[test]
+    fn parses_other_settings() {
+        assert_eq!(
+            parse_treshold_depths("30, 10,,20,10"),
+            Some(vec![10., 20., 30.])
+        );
+        assert_eq!(parse_treshold_depths("10,-5"), None);
+        assert_eq!(
+            parse_ascent_rate_limits("30:18,0:9"),
+            Some(vec![(0., 9.), (30., 18.)])
+        );
+        assert_eq!(parse_ascent_rate_limits("9"), Some(vec![(0., 9.)]));
+        assert_eq!(parse_ascent_rate_limits("0:0"), None);
+        assert_eq!(parse_ascent_rate_limits(""), None);
+        assert_eq!(
+            parse_water_density("Salt"),
+            Some(common::SALT_WATER_DENSITY)
+        );
+        assert_eq!(parse_water_density("1010"), Some(1010.));
+        assert_eq!(parse_water_density("1200"), None);
+        assert_eq!(parse_pp_o2_max("1.4"), Some(1.4));
+        assert_eq!(parse_pp_o2_max("2.1"), None);
+        assert_eq!(
+            parse_temperature_unit("F"),
+            Some(TemperatureUnit::Fahrenheit)
+        );
+        assert_eq!(parse_temperature_unit("k"), None);
+    }
+
+    #[test]
+    fn skips_dive_without_samples() {
+        let stats = Stats::new();
+        let no_samples = dive_elem("<dive><informationbeforedive/></dive>");
+        let empty_samples = dive_elem("<dive><informationbeforedive/><samples/></dive>");
+        for dive_data in [no_samples, empty_samples] {
+            let error = calc_dive_stats(&stats, &dive_data, &None).err();
+            assert_eq!(error, Some(AnalysisError::NoSamples));
+        }
+    }
+
+    #[test]
+    fn skips_dive_with_missing_gas_ref() {
+        let stats = Stats::new();
+        let dive_data = dive_elem(
+            "<dive><informationbeforedive/><samples>\
+            <waypoint><divetime>0</divetime><depth>0</depth><switchmix ref=\"ean32\"/></waypoint>\
+            <waypoint><divetime>60</divetime><depth>10</depth></waypoint>\
+            </samples></dive>",
+        );
+        let error = calc_dive_stats(&stats, &dive_data, &Some(vec![mix("air", 0.21, None)])).err();
+        assert_eq!(error, Some(AnalysisError::GasNotFound("ean32".to_string())));
+        let error = calc_dive_stats(&stats, &dive_data, &None).err();
+        assert_eq!(error, Some(AnalysisError::NoGasMixes("ean32".to_string())));
+        let dive_data = dive_elem(
+            "<dive><informationbeforedive/><samples>\
+            <waypoint><divetime>0</divetime><depth>0</depth><switchmix ref=\"tx\"/></waypoint>\
+            </samples></dive>",
+        );
+        let error =
+            calc_dive_stats(&stats, &dive_data, &Some(vec![mix("tx", 0.6, Some(0.6))])).err();
+        assert_eq!(error, Some(AnalysisError::InvalidGasMix("tx".to_string())));
+    }
+
+    #[test]
+    fn falls_back_to_configured_surface_pressure_with_warning() {
+        let stats = Stats::new();
+        let dive_data = |surface_pressure: &str| {
+            dive_elem(&format!(
+                "<dive><informationbeforedive><surfacepressure>{surface_pressure}</surfacepressure>\
+                </informationbeforedive><samples>\
+                <waypoint><divetime>0</divetime><depth>0</depth></waypoint>\
+                <waypoint><divetime>60</divetime><depth>10</depth></waypoint>\
+                </samples></dive>"
+            ))
+        };
+        for surface_pressure in ["40000", "200000", "1013hPa", "NaN"] {
+            let (dive, _) = calc_dive_stats(&stats, &dive_data(surface_pressure), &None).unwrap();
+            assert_eq!(dive.surface_pressure, common::DEFAULT_SURFACE_PRESSURE);
+            assert_eq!(
+                dive.warnings,
+                vec![AnalysisError::InvalidSurfacePressure(
+                    surface_pressure.to_string()
+                )]
+            );
+        }
+        let (dive, _) = calc_dive_stats(&stats, &dive_data("90000"), &None).unwrap();
+        assert_eq!(dive.surface_pressure, 900);
+        assert!(dive.warnings.is_empty());
+    }
+
+    #[test]
+    fn reads_water_density_from_dive_or_site_environment() {
+        let stats = Stats::with_config(StatsConfig {
+            water_density: Some(1010.),
+            ..StatsConfig::default()
+        });
+        let sites = |environment: &str| {
+            vec![SiteElem {
+                id: "s1".to_string(),
+                name: None,
+                geography: None,
+                environment: Some(environment.to_string()),
+            }]
+        };
+        let dive_data = |info: &str| {
+            dive_elem(&format!(
+                "<dive><informationbeforedive>{info}</informationbeforedive></dive>"
+            ))
+        };
+        let density = |info: &str, environment: &str| {
+            stats.dive_water_density(&dive_data(info), &sites(environment))
+        };
+        let site_link = "<link ref=\"s1\"/>";
+        assert_eq!(
+            density("<density>1020</density>", "lake-quarry"),
+            Some(1020.)
+        );
+        assert_eq!(
+            density(site_link, "ocean-sea"),
+            Some(common::SALT_WATER_DENSITY)
+        );
+        assert_eq!(
+            density(site_link, "river-spring"),
+            Some(common::FRESH_WATER_DENSITY)
+        );
+        assert_eq!(
+            density(&format!("{site_link}<density>1030</density>"), "pool"),
+            Some(1030.)
+        );
+        // site without known water, not linked site, invalid density
+        assert_eq!(density(site_link, "cave-cavern"), Some(1010.));
+        assert_eq!(density("", "ocean-sea"), Some(1010.));
+        assert_eq!(
+            density(&format!("{site_link}<density>salty</density>"), "pool"),
+            Some(common::FRESH_WATER_DENSITY)
+        );
+    }
+
+    #[test]
+    fn carries_cns_over_surface_interval() {
+        let stats = Stats::new();
+        let gas_mixes = Some(vec![mix("ean50", 0.5, None)]);
+        let first_dive = dive_elem(
+            "<dive><informationbeforedive/><samples>\
+            <waypoint><divetime>0</divetime><depth>0</depth><switchmix ref=\"ean50\"/></waypoint>\
+            <waypoint><divetime>60</divetime><depth>20</depth></waypoint>\
+            <waypoint><divetime>1260</divetime><depth>20</depth></waypoint>\
+            <waypoint><divetime>1320</divetime><depth>0</depth></waypoint>\
+            </samples></dive>",
+        );
+        let (first_dive, tissues) = calc_dive_stats(&stats, &first_dive, &gas_mixes).unwrap();
+        let cns = tissues.ox_tox.cns;
+        assert!(cns > 0. && first_dive.cns_max == cns);
+        // shallow air dive 90 minutes later, CNS halved and not increased
+        let second_dive = dive_elem(
+            "<dive><informationbeforedive><surfaceintervalbeforedive>\
+            <passedtime>5400</passedtime></surfaceintervalbeforedive></informationbeforedive>\
+            <samples><waypoint><divetime>0</divetime><depth>0</depth></waypoint>\
+            <waypoint><divetime>600</divetime><depth>5</depth></waypoint>\
+            </samples></dive>",
+        );
+        let (second_dive, tissues) = stats
+            .calc_dive_stats(
+                &second_dive,
+                &gas_mixes,
+                &None,
+                &vec![],
+                &None,
+                Some(tissues),
+            )
+            .unwrap();
+        assert_eq!(second_dive.surface_interval, Some(5400));
+        assert!((second_dive.cns_max - cns / 2.).abs() < 1e-9);
+        assert_eq!(second_dive.otu, 0.);
+        assert!((tissues.ox_tox.otu - first_dive.otu).abs() < 1e-9);
+    }
+
+    #[test]
+    fn averages_site_depth_over_dive_time() {
+        let stats = Stats::new();
+        let dive_data = |depth: Depth| {
+            dive_elem(&format!(
+                "<dive><informationbeforedive/><samples>\
+                <waypoint><divetime>0</divetime><depth>0</depth></waypoint>\
+                <waypoint><divetime>60</divetime><depth>{depth}</depth></waypoint>\
+                <waypoint><divetime>1260</divetime><depth>{depth}</depth></waypoint>\
+                <waypoint><divetime>1320</divetime><depth>0</depth></waypoint>\
+                </samples></dive>"
+            ))
+        };
+        let (deep_dive, _) = calc_dive_stats(&stats, &dive_data(22.), &None).unwrap();
+        let (shallow_dive, _) = calc_dive_stats(&stats, &dive_data(11.), &None).unwrap();
+        // 22m for 20 minutes and 1 minute descent and ascent at 11m average
+        assert!((deep_dive.depth_avg - 21.).abs() < 1e-9);
+        assert!((shallow_dive.depth_avg - 10.5).abs() < 1e-9);
+        let site = Site {
+            id: "site".to_string(),
+            name: Some("Reef".to_string()),
+            location: None,
+            latitude: None,
+            longitude: None,
+            altitude: None,
+        };
+        let mut site_stats = SiteStats::new(site.clone(), &deep_dive);
+        site_stats.merge(SiteStats::new(site, &shallow_dive));
+        assert_eq!(site_stats.depth_max, 22.);
+        assert!((site_stats.depth_avg.value().unwrap() - 15.75).abs() < 1e-9);
+    }
+
+    #[test]
+    fn skips_dive_with_invalid_samples() {
+        let stats = Stats::new();
+        let dive_data = dive_elem(
+            "<dive><informationbeforedive/><samples>\
+            <waypoint><divetime>60</divetime><depth>10</depth></waypoint>\
+            <waypoint><divetime>30</divetime><depth>10</depth></waypoint>\
+            </samples></dive>",
+        );
+        let error = calc_dive_stats(&stats, &dive_data, &None).err();
+        assert_eq!(error, Some(AnalysisError::InvalidTime(30)));
+        let dive_data = dive_elem(
+            "<dive><informationbeforedive/><samples>\
+            <waypoint><divetime>0</divetime><depth>-3</depth></waypoint>\
+            </samples></dive>",
+        );
+        let error = calc_dive_stats(&stats, &dive_data, &None).err();
+        assert_eq!(error, Some(AnalysisError::InvalidDepth(0, -3.)));
+    }
+
+    fn equipment(
+        purchase_date: Option<&str>,
+        service_interval_days: Option<u32>,
+        next_service_date: Option<&str>,
+    ) -> Equipment {
+        Equipment {
+            id: "reg".to_string(),
+            kind: "regulator".to_string(),
+            name: Some("Regulator".to_string()),
+            manufacturer: None,
+            model: None,
+            serial_number: Some("123".to_string()),
+            purchase_date: purchase_date.map(str::to_string),
+            service_interval_days,
+            next_service_date: next_service_date.map(str::to_string),
+        }
+    }
+
+    // dated dives and one undated
+    fn equipment_stats(item: Equipment, dive_dates: &[&str]) -> EquipmentStats {
+        EquipmentStats {
+            item,
+            dives_no: dive_dates.len() + 1,
+            total_time: 3600,
+            dive_dates: dive_dates.iter().map(|date| date.to_string()).collect(),
+        }
+    }
+
+    fn timestamp(date_time: &str) -> Timestamp {
+        common::parse_timestamp(date_time).unwrap()
+    }
+
+    #[test]
+    fn counts_dives_since_service() {
+        let dive_dates = [
+            "2023-05-01T10:00:00",
+            "2023-06-10T10:00:00",
+            "2023-08-01T10:00:00",
+        ];
+        // last service a year before next one
+        let serviced = equipment(Some("2022-01-01"), Some(365), Some("2024-06-01"));
+        assert_eq!(
+            serviced.last_service_timestamp(),
+            Some(timestamp("2023-06-02"))
+        );
+        assert_eq!(
+            equipment_stats(serviced, &dive_dates).dives_since_service(),
+            2
+        );
+        // purchase as last service without service interval
+        let purchased = equipment(Some("2023-06-01"), None, Some("2024-06-01"));
+        assert_eq!(
+            equipment_stats(purchased, &dive_dates).dives_since_service(),
+            2
+        );
+        // all dives, undated ones included, if last service unknown
+        let unknown = equipment(None, None, None);
+        assert_eq!(
+            equipment_stats(unknown, &dive_dates).dives_since_service(),
+            4
+        );
+    }
+
+    #[test]
+    fn warns_from_crossed_service_interval() {
+        let config = StatsConfig {
+            service_dives: Some(3),
+            service_days: Some(100),
+            ..StatsConfig::default()
+        };
+        let item = equipment(Some("2023-06-01"), None, None);
+        let before_due = timestamp("2023-09-08");
+        let due = timestamp("2023-09-09");
+        assert_eq!(
+            equipment_stats(item.clone(), &["2023-06-10", "2023-07-01"])
+                .service_warnings(&config, before_due),
+            Vec::<String>::new()
+        );
+        assert_eq!(
+            equipment_stats(item.clone(), &["2023-06-10", "2023-07-01"])
+                .service_warnings(&config, due),
+            vec!["service due since 2023-09-09".to_string()]
+        );
+        assert_eq!(
+            equipment_stats(item, &["2023-06-10", "2023-07-01", "2023-08-01"])
+                .service_warnings(&config, before_due),
+            vec!["3 dive(s) since service, interval 3".to_string()]
+        );
+        // piece next service date and interval over configured interval
+        let item = equipment(None, Some(30), Some("2023-07-01"));
+        assert_eq!(
+            equipment_stats(item, &["2023-06-10"])
+                .service_warnings(&config, timestamp("2023-07-01")),
+            vec!["service due since 2023-07-01".to_string()]
+        );
+    }
+
+    #[test]
+    fn no_service_date_warning_without_service_date() {
+        let config = StatsConfig {
+            service_dives: Some(3),
+            service_days: Some(100),
+            ..StatsConfig::default()
+        };
+        let item = equipment(None, Some(365), None);
+        let equipment_stats = equipment_stats(item, &["2023-06-10", "2023-07-01"]);
+        assert_eq!(
+            equipment_stats.service_due_timestamp(config.service_days),
+            None
+        );
+        // undated dive counted
+        assert_eq!(
+            equipment_stats.service_warnings(&config, timestamp("2030-01-01")),
+            vec!["3 dive(s) since service, interval 3".to_string()]
+        );
+    }
+
+    #[test]
+    fn merges_equipment_stats_from_files() {
+        let earlier_service = equipment(Some("2022-01-01"), Some(365), Some("2023-06-01"));
+        let later_service = equipment(Some("2022-01-01"), Some(365), Some("2024-06-01"));
+        let mut merged = equipment_stats(later_service.clone(), &["2023-08-01", "2023-06-10"]);
+        merged.merge(equipment_stats(earlier_service.clone(), &["2023-07-01"]));
+        assert_eq!(
+            merged,
+            EquipmentStats {
+                item: later_service.clone(),
+                dives_no: 5,
+                total_time: 7200,
+                dive_dates: vec![
+                    "2023-06-10".to_string(),
+                    "2023-07-01".to_string(),
+                    "2023-08-01".to_string()
+                ],
+            }
+        );
+        // last service from kept piece data, dives from both files
+        assert_eq!(merged.dives_since_service(), 3);
+        // merge order independent
+        let mut merged_other_way = equipment_stats(earlier_service, &["2023-07-01"]);
+        merged_other_way.merge(equipment_stats(
+            later_service,
+            &["2023-08-01", "2023-06-10"],
+        ));
+        assert_eq!(merged_other_way, merged);
+    }
+
+    #[test]
+    fn leaves_out_file_analysed_after_cancel() {
+        let path = concat!(
+            env!("CARGO_MANIFEST_DIR"),
+            "/tests/fixtures/logbook/2023.uddf"
+        );
+        let stats = Stats::new();
+        assert_eq!(stats.file_stats(path).sources, vec![path.to_string()]);
+        stats.progress.cancel();
+        let file_stats = stats.file_stats(path);
+        assert!(file_stats.sources.is_empty());
+        assert!(file_stats.dives.is_empty());
+    }
+}
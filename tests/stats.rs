@@ -0,0 +1,241 @@
+use dive_reporter::{Dive, Stats, StatsConfig, StatsData};
+use std::sync::atomic::Ordering;
+
+const LOGBOOK: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/logbook");
+
+fn analyse(path: &str) -> Stats {
+    let config = StatsConfig {
+        keep_samples: true,
+        ..StatsConfig::default()
+    };
+    Stats::with_config(config).from_path(path).unwrap()
+}
+
+fn stats_data(path: &str) -> StatsData {
+    analyse(path).stats_data.lock().unwrap().clone()
+}
+
+#[test]
+fn combines_files_and_folders_into_one_analysis() {
+    let stats = Stats::with_config(StatsConfig::default());
+    let paths = [format!("{LOGBOOK}/2023.uddf"), format!("{LOGBOOK}/trips")];
+    stats.load_paths(&paths).unwrap();
+    let dives_no = stats.stats_data.lock().unwrap().dives_no;
+    assert_eq!(
+        stats.loaded_sources(),
+        vec![
+            format!("{LOGBOOK}/2023.uddf"),
+            format!("{LOGBOOK}/trips/broken.uddf"),
+            format!("{LOGBOOK}/trips/red-sea.UDDF"),
+        ]
+    );
+    // only file not loaded yet analysed when whole logbook added
+    stats.load_paths(&[LOGBOOK.to_string()]).unwrap();
+    assert_eq!(stats.progress.files_total(), 1);
+    assert_eq!(stats.loaded_sources().len(), 4);
+    assert!(stats.stats_data.lock().unwrap().dives_no > dives_no);
+    // removed file dropped without analysing remaining ones again
+    stats.remove_sources(|source| source.ends_with("2024.uddf"));
+    assert_eq!(stats.loaded_sources().len(), 3);
+    assert_eq!(stats.stats_data.lock().unwrap().dives_no, dives_no);
+}
+
+#[test]
+fn merges_file_stats_into_logbook_stats() {
+    let logbook = stats_data(LOGBOOK);
+    let merged = ["2023.uddf", "2024.uddf", "trips/red-sea.UDDF"]
+        .iter()
+        .map(|file| stats_data(&format!("{LOGBOOK}/{file}")))
+        .fold(StatsData::default(), StatsData::merge);
+    assert_eq!(merged.dives_no, logbook.dives_no);
+    assert_eq!(merged.total_time, logbook.total_time);
+    assert_eq!(merged.depth_max, logbook.depth_max);
+    assert_eq!(merged.time_in_deco, logbook.time_in_deco);
+    assert_eq!(merged.deco_dives_no, logbook.deco_dives_no);
+    assert_eq!(merged.gf_surf_max, logbook.gf_surf_max);
+    assert_eq!(merged.time_below, logbook.time_below);
+    assert_eq!(merged.cns_max, logbook.cns_max);
+    assert_eq!(merged.ceiling_max, logbook.ceiling_max);
+    assert_eq!(merged.dives.len(), logbook.dives.len());
+}
+
+#[test]
+fn combines_sites_with_same_name_and_location_across_files() {
+    let stats = stats_data(LOGBOOK);
+    let sites: Vec<_> = stats
+        .sites
+        .iter()
+        .map(|(name, site_stats)| {
+            (
+                name.as_str(),
+                site_stats.dives_no,
+                site_stats.depth_max,
+                site_stats.total_time,
+            )
+        })
+        .collect();
+    assert_eq!(
+        sites,
+        vec![
+            ("Blue Hole, Dahab", 6, 30., 10900),
+            ("Canyon, Dahab", 4, 30., 9140),
+        ]
+    );
+    let (_, blue_hole) = &stats.sites[0];
+    assert_eq!(
+        blue_hole.last_visited.as_deref(),
+        Some("2024-09-04T11:00:00")
+    );
+}
+
+#[test]
+fn output_is_the_same_in_every_run() {
+    let stats = analyse(LOGBOOK);
+    let output = stats.to_output();
+    let csv = stats.to_csv();
+    let timeline = stats.timeline_to_csv();
+    assert_eq!(stats.stats_data.lock().unwrap().dives_no, 10);
+    for _ in 0..5 {
+        let stats = analyse(LOGBOOK);
+        assert_eq!(stats.to_output(), output);
+        assert_eq!(stats.to_csv(), csv);
+        assert_eq!(stats.timeline_to_csv(), timeline);
+    }
+}
+
+#[test]
+fn drops_samples_unless_kept() {
+    let stats = Stats::with_config(StatsConfig::default())
+        .from_path(LOGBOOK)
+        .unwrap();
+    let stats_data = stats.stats_data.lock().unwrap();
+    assert!(stats_data.dives.iter().all(|dive| dive.timeline.is_empty()));
+    assert!(stats_data
+        .dives
+        .iter()
+        .all(|dive| dive.tissues_at(600).is_none()));
+    drop(stats_data);
+    assert_eq!(stats.to_output(), analyse(LOGBOOK).to_output());
+}
+
+#[test]
+fn merged_records_do_not_depend_on_merge_order() {
+    let files = ["2023.uddf", "2024.uddf", "trips/red-sea.UDDF"];
+    let parts: Vec<StatsData> = files
+        .iter()
+        .map(|file| stats_data(&format!("{LOGBOOK}/{file}")))
+        .collect();
+    let merged = parts
+        .iter()
+        .cloned()
+        .fold(StatsData::default(), StatsData::merge);
+    let merged_reversed = parts
+        .iter()
+        .rev()
+        .cloned()
+        .fold(StatsData::default(), StatsData::merge);
+    assert_eq!(merged.divers, merged_reversed.divers);
+    // float averages summed in merge order, only records picked on merge compared
+    let sites = |stats: &StatsData| {
+        stats
+            .sites
+            .iter()
+            .map(|(name, site_stats)| {
+                (
+                    name.clone(),
+                    site_stats.site.clone(),
+                    site_stats.dives_no,
+                    site_stats.last_visited.clone(),
+                )
+            })
+            .collect::<Vec<_>>()
+    };
+    assert_eq!(sites(&merged), sites(&merged_reversed));
+    assert_eq!(merged.buddies, merged_reversed.buddies);
+    assert_eq!(merged.equipment, merged_reversed.equipment);
+    assert_eq!(
+        merged.temperature_min_date,
+        merged_reversed.temperature_min_date
+    );
+    // deepest dives with buddy are equal, earliest one kept
+    let (_, buddy_stats) = &merged.buddies[0];
+    assert_eq!(buddy_stats.depth_max, 30.);
+    assert_eq!(
+        buddy_stats.depth_max_date.as_deref(),
+        Some("2023-06-01T09:00:00")
+    );
+}
+
+#[test]
+fn skips_malformed_file() {
+    let stats = stats_data(LOGBOOK);
+    assert_eq!(stats.skipped.len(), 1);
+    assert!(stats.skipped[0].source.ends_with("broken.uddf"));
+    assert_eq!(stats.skipped[0].dive, None);
+    assert_eq!(stats.sources.len(), 4);
+}
+
+#[test]
+fn keeps_source_with_all_dives_filtered_out() {
+    let config = StatsConfig {
+        buddy: Some("nobody".to_string()),
+        ..StatsConfig::default()
+    };
+    let stats = Stats::with_config(config);
+    let path = format!("{LOGBOOK}/trips/red-sea.UDDF");
+    stats.load_paths(std::slice::from_ref(&path)).unwrap();
+    assert_eq!(stats.stats_data.lock().unwrap().dives_no, 0);
+    assert_eq!(stats.loaded_sources(), vec![path.clone()]);
+    // not analysed again when added again
+    stats.load_paths(&[path]).unwrap();
+    assert_eq!(stats.progress.files_total(), 0);
+}
+
+#[test]
+fn progress_reaches_size_of_all_files() {
+    let stats = analyse(LOGBOOK);
+    let bytes_total: u64 = [
+        "2023.uddf",
+        "2024.uddf",
+        "trips/broken.uddf",
+        "trips/red-sea.UDDF",
+    ]
+    .iter()
+    .map(|file| {
+        std::fs::metadata(format!("{LOGBOOK}/{file}"))
+            .unwrap()
+            .len()
+    })
+    .sum();
+    let progress = &stats.progress;
+    assert_eq!(progress.bytes_total.load(Ordering::Relaxed), bytes_total);
+    assert_eq!(progress.bytes_parsed.load(Ordering::Relaxed), bytes_total);
+    assert_eq!(progress.fraction(), 1.);
+}
+
+#[test]
+fn analyses_dive_again_with_samples() {
+    let kept = stats_data(LOGBOOK);
+    let stats = Stats::with_config(StatsConfig::default());
+    for dive in &kept.dives {
+        let analysed = stats
+            .dive_with_samples(&dive.source, dive.repetition_group, dive.group_index)
+            .unwrap();
+        assert_eq!(analysed.timeline, dive.timeline);
+        assert_eq!(analysed.gf_surf_max, dive.gf_surf_max);
+        assert_eq!(analysed.source, dive.source);
+        let tissues = |dive: &Dive| {
+            dive.tissues_at(dive.total_time).map(|tissues| {
+                tissues
+                    .compartments
+                    .iter()
+                    .map(|c| c.n2_ip)
+                    .collect::<Vec<_>>()
+            })
+        };
+        assert!(tissues(&analysed).is_some());
+        assert_eq!(tissues(&analysed), tissues(dive));
+    }
+    let source = &kept.dives[0].source;
+    assert!(stats.dive_with_samples(source, 99, 0).is_err());
+}